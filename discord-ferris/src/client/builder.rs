@@ -0,0 +1,274 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::discord_ferris::log::Log;
+use crate::gateway::encoding::GatewayEncoding;
+use crate::gateway::identify::{IdentifyConfig, IdentifyQueue};
+use crate::gateway::pipeline::{DEFAULT_EVENT_CAPACITY, EventPolicies, OverflowPolicy};
+use crate::gateway::record::Recorder;
+use crate::gateway::shard::{ReconnectPolicy, ShardId, ShardRange, presence_update};
+use crate::gateway::store::SessionStore;
+use crate::http::proxy::Proxy;
+use crate::http::{DISCORD_API_BASE, Http};
+use crate::log;
+
+use super::{Client, ShutdownHandle};
+use crate::framework::context::{Context, Ctx};
+use crate::framework::router::Router;
+use crate::models::gateway::{
+    GatewayActivityUpdateData, GatewayDispatchEvents, GatewayIdentifyProperties, GatewayIntents,
+    GatewayPresenceUpdateData,
+};
+use crate::models::payloads::PresenceUpdateStatus;
+
+/// Configures a [`Client`]: every IDENTIFY field plus connection settings.
+#[derive(Clone)]
+pub struct ClientBuilder {
+    identify: IdentifyConfig,
+    shards: ShardRange,
+    presence: Option<GatewayPresenceUpdateData>,
+    identify_queue: Option<Arc<dyn IdentifyQueue>>,
+    encoding: GatewayEncoding,
+    transport_compression: bool,
+    gateway_url: Option<String>,
+    api_base_url: String,
+    reconnect: ReconnectPolicy,
+    event_capacity: usize,
+    event_policies: EventPolicies,
+    session_store: Option<Arc<dyn SessionStore>>,
+    recorder: Option<Arc<Recorder>>,
+    proxy: Option<Proxy>,
+    ctrl_c: bool,
+    shutdown_timeout: Duration,
+}
+
+/// How long [`Client::login`] lets handlers drain queued dispatches on shutdown.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl ClientBuilder {
+    pub fn new(token: impl Into<String>, intents: GatewayIntents) -> Self {
+        Self {
+            identify: IdentifyConfig::new(token, intents),
+            shards: ShardRange::default(),
+            presence: None,
+            identify_queue: None,
+            encoding: GatewayEncoding::default(),
+            transport_compression: cfg!(feature = "zlib-stream"),
+            gateway_url: None,
+            api_base_url: DISCORD_API_BASE.to_owned(),
+            reconnect: ReconnectPolicy::default(),
+            event_capacity: DEFAULT_EVENT_CAPACITY,
+            event_policies: EventPolicies::default(),
+            session_store: None,
+            recorder: None,
+            proxy: None,
+            ctrl_c: true,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+        }
+    }
+
+    pub fn token(&mut self, token: impl Into<String>) -> &mut Self {
+        self.identify.token = token.into();
+        self
+    }
+
+    pub fn intents(&mut self, intents: GatewayIntents) -> &mut Self {
+        self.identify.intents = intents;
+        self
+    }
+
+    /// Connection properties; defaults to the current OS and `discord-ferris`.
+    pub fn properties(&mut self, properties: GatewayIdentifyProperties) -> &mut Self {
+        self.identify.properties = properties;
+        self
+    }
+
+    /// Compresses each payload on its own instead of the whole connection.
+    ///
+    /// Needs the `zlib-stream` feature; ignored without it.
+    pub fn compress(&mut self, compress: bool) -> &mut Self {
+        if compress && !cfg!(feature = "zlib-stream") {
+            log!(
+                "WARN",
+                "payload compression needs the `zlib-stream` feature; ignoring"
+            );
+            return self;
+        }
+        self.identify.compress = compress;
+        self
+    }
+
+    /// Compresses the whole connection with `compress=zlib-stream`; on by
+    /// default with the `zlib-stream` feature, which it needs.
+    pub fn transport_compression(&mut self, enabled: bool) -> &mut Self {
+        if enabled && !cfg!(feature = "zlib-stream") {
+            log!(
+                "WARN",
+                "transport compression needs the `zlib-stream` feature; ignoring"
+            );
+            return self;
+        }
+        self.transport_compression = enabled;
+        self
+    }
+
+    /// Member count (50–250) above which guilds omit offline members.
+    pub fn large_threshold(&mut self, threshold: i64) -> &mut Self {
+        self.identify.large_threshold = Some(threshold.clamp(50, 250));
+        self
+    }
+
+    /// Runs the single shard `id` out of `total`.
+    pub fn shard(&mut self, id: ShardId, total: ShardId) -> &mut Self {
+        self.shards(ShardRange::Range {
+            ids: id..id + 1,
+            total,
+        })
+    }
+
+    /// Which shards to run; defaults to the count recommended by GET /gateway/bot.
+    pub fn shards(&mut self, range: ShardRange) -> &mut Self {
+        self.shards = range;
+        self
+    }
+
+    /// Status and activity sent with IDENTIFY.
+    pub fn presence(
+        &mut self,
+        status: PresenceUpdateStatus,
+        activity: Option<GatewayActivityUpdateData>,
+    ) -> &mut Self {
+        self.presence = Some(presence_update(status, activity));
+        self
+    }
+
+    /// Coordinates IDENTIFY across processes; defaults to an in-process queue
+    /// built from `session_start_limit`.
+    pub fn identify_queue(&mut self, queue: Arc<dyn IdentifyQueue>) -> &mut Self {
+        self.identify_queue = Some(queue);
+        self
+    }
+
+    /// Gateway payload encoding (`json` by default, or `etf`).
+    pub fn encoding(&mut self, encoding: GatewayEncoding) -> &mut Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Connects to `url` instead of the URL returned by GET /gateway/bot.
+    pub fn gateway_url(&mut self, url: impl Into<String>) -> &mut Self {
+        self.gateway_url = Some(url.into());
+        self
+    }
+
+    /// REST base URL; defaults to [`DISCORD_API_BASE`].
+    pub fn api_base_url(&mut self, url: impl Into<String>) -> &mut Self {
+        self.api_base_url = url.into();
+        self
+    }
+
+    /// Backoff between failed connection attempts.
+    pub fn reconnect_policy(&mut self, policy: ReconnectPolicy) -> &mut Self {
+        self.reconnect = policy;
+        self
+    }
+
+    /// Dispatches buffered between the shards and the handlers.
+    pub fn event_capacity(&mut self, capacity: usize) -> &mut Self {
+        self.event_capacity = capacity.max(1);
+        self
+    }
+
+    /// What to do with `kind` dispatches while the event queue is full,
+    /// e.g. shed `TypingStart` floods with [`OverflowPolicy::DropNewest`].
+    pub fn event_policy(
+        &mut self,
+        kind: GatewayDispatchEvents,
+        policy: OverflowPolicy,
+    ) -> &mut Self {
+        self.event_policies.set(kind, policy);
+        self
+    }
+
+    /// Policy for kinds without their own; defaults to [`OverflowPolicy::Block`].
+    pub fn default_event_policy(&mut self, policy: OverflowPolicy) -> &mut Self {
+        self.event_policies.default = policy;
+        self
+    }
+
+    /// Keeps sessions across restarts: shards save them on shutdown and
+    /// RESUME them on the next start, e.g. with a
+    /// [`FileSessionStore`](crate::gateway::store::FileSessionStore).
+    pub fn session_store(&mut self, store: Arc<dyn SessionStore>) -> &mut Self {
+        self.session_store = Some(store);
+        self
+    }
+
+    /// Sends REST requests and gateway connections through `proxy`.
+    pub fn proxy(&mut self, proxy: Proxy) -> &mut Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Appends every frame the shards receive to a JSONL file; play it back
+    /// with [`Client::replay`].
+    pub fn recorder(&mut self, recorder: Recorder) -> &mut Self {
+        self.recorder = Some(Arc::new(recorder));
+        self
+    }
+
+    /// Whether [`Client::login`] shuts down on Ctrl+C (on by default). Turn it
+    /// off to stop only through [`Client::shutdown_handle`].
+    pub fn ctrl_c(&mut self, enabled: bool) -> &mut Self {
+        self.ctrl_c = enabled;
+        self
+    }
+
+    /// How long handlers may keep draining queued dispatches once shutdown
+    /// starts; defaults to [`DEFAULT_SHUTDOWN_TIMEOUT`].
+    pub fn shutdown_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Fails if the HTTP client cannot be built, e.g. because reqwest rejects
+    /// the [`proxy`](Self::proxy).
+    pub fn build(&self) -> reqwest::Result<Client> {
+        let mut router = Router::new();
+        router.on_all(|c| async move {
+            if let Some(t) = c.event_name() {
+                crate::log!("EVT", "[shard {}] {:?}", c.shard_id(), t);
+            }
+        });
+
+        let http = Arc::new(Http::with_proxy(
+            &self.identify.token,
+            &self.api_base_url,
+            self.proxy.as_ref(),
+        )?);
+        let inner_ctx = Arc::new(Context::new(Arc::clone(&http)));
+        let ctx = Ctx::new(inner_ctx);
+
+        Ok(Client {
+            identify: self.identify.clone(),
+            shards: self.shards.clone(),
+            identify_queue: self.identify_queue.clone(),
+            encoding: self.encoding,
+            transport_compression: self.transport_compression,
+            presence: self.presence.clone(),
+            gateway_url: self.gateway_url.clone(),
+            reconnect: self.reconnect.clone(),
+            event_capacity: self.event_capacity,
+            event_policies: self.event_policies.clone(),
+            session_store: self.session_store.clone(),
+            recorder: self.recorder.clone(),
+            proxy: self.proxy.clone(),
+            ctrl_c: self.ctrl_c,
+            shutdown: ShutdownHandle::new(),
+            shutdown_timeout: self.shutdown_timeout,
+            log: Log {},
+            router,
+            ctx,
+        })
+    }
+}
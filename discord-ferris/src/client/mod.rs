@@ -1,32 +1,52 @@
+use serde_json::value::RawValue;
 use std::future::Future;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::discord_ferris::log::Log;
-use crate::gateway::{self, Gateway};
-use crate::http::Http;
+use crate::gateway::GatewayError;
+use crate::gateway::encoding::GatewayEncoding;
+use crate::gateway::identify::{IdentifyConfig, IdentifyQueue};
+use crate::gateway::pipeline::{EventPolicies, EventStats};
+use crate::gateway::record::{Recorder, Replay};
+use crate::gateway::shard::{
+    CloseMode, ReconnectPolicy, ShardManager, ShardRange, ShardStats, presence_update,
+};
+use crate::gateway::store::SessionStore;
+use crate::http::proxy::Proxy;
 use crate::log;
 
-use crate::framework::context::{Context, Ctx};
+use crate::framework::context::Ctx;
+use crate::framework::event::{Event, EventStream};
 use crate::framework::router::Router;
-use crate::models::gateway::{GatewayDispatchEvents, GatewayIntents};
-
-#[inline]
-fn jitter(min: u64, max: u64) -> u64 {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let nanos = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .subsec_nanos() as u64;
-    let mut state = nanos ^ 0x5DEECE66D;
-    state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
-    min + (state % (max - min + 1))
-}
+
+mod builder;
+mod shutdown;
+use crate::models::gateway::{
+    GatewayActivityUpdateData, GatewayDispatchEvents, GatewayIntents, GatewayPresenceUpdateData,
+};
+use crate::models::payloads::PresenceUpdateStatus;
+pub use builder::{ClientBuilder, DEFAULT_SHUTDOWN_TIMEOUT};
+pub use shutdown::ShutdownHandle;
 
 /// High-level client.
 pub struct Client {
-    token: String,
-    intents: GatewayIntents,
-    gateway: Option<Gateway>,
+    identify: IdentifyConfig,
+    shards: ShardRange,
+    identify_queue: Option<Arc<dyn IdentifyQueue>>,
+    encoding: GatewayEncoding,
+    transport_compression: bool,
+    presence: Option<GatewayPresenceUpdateData>,
+    gateway_url: Option<String>,
+    reconnect: ReconnectPolicy,
+    event_capacity: usize,
+    event_policies: EventPolicies,
+    session_store: Option<Arc<dyn SessionStore>>,
+    recorder: Option<Arc<Recorder>>,
+    proxy: Option<Proxy>,
+    ctrl_c: bool,
+    shutdown: ShutdownHandle,
+    shutdown_timeout: Duration,
     #[allow(dead_code)]
     log: Log,
     router: Router,
@@ -34,28 +54,43 @@ pub struct Client {
 }
 
 impl Client {
+    /// Client with default settings; see [`Client::builder`] for the rest.
     pub fn new(token: impl Into<String>, intents: GatewayIntents) -> Self {
-        let token = token.into();
+        ClientBuilder::new(token, intents)
+            .build()
+            .expect("failed to build HTTP Client")
+    }
 
-        let mut router = Router::new();
-        router.on_all(|c| async move {
-            if let Some(t) = c.event_name() {
-                crate::log!("EVT", "{:?}", t);
-            }
-        });
-
-        let http = Arc::new(Http::new(&token));
-        let inner_ctx = Arc::new(Context::new(Arc::clone(&http)));
-        let ctx = Ctx::new(inner_ctx);
-
-        Self {
-            token,
-            intents,
-            gateway: None,
-            log: Log {},
-            router,
-            ctx,
+    pub fn builder(token: impl Into<String>, intents: GatewayIntents) -> ClientBuilder {
+        ClientBuilder::new(token, intents)
+    }
+
+    /// Sets the bot's status and activity on every shard.
+    ///
+    /// Before [`login`](Self::login) this becomes the presence sent with
+    /// IDENTIFY; from handlers use [`Ctx::set_presence`] instead.
+    pub fn set_presence(
+        &mut self,
+        status: PresenceUpdateStatus,
+        activity: Option<GatewayActivityUpdateData>,
+    ) -> &mut Self {
+        let presence = presence_update(status, activity);
+        if let Err(e) = self.ctx.shards().update_presence(presence.clone()) {
+            log!("ERR", "presence update failed: {e}");
         }
+        self.presence = Some(presence);
+        self
+    }
+
+    /// Event queue depth and dropped dispatches; clone it before
+    /// [`login`](Self::login) to watch it from another task.
+    pub fn event_stats(&self) -> Arc<EventStats> {
+        Arc::clone(self.ctx.inner.events())
+    }
+
+    /// Latency and reconnect stats of every running shard.
+    pub fn shard_stats(&self) -> Vec<ShardStats> {
+        self.ctx.shards().stats()
     }
 
     pub fn on<T, F, Fut>(&mut self, kind: GatewayDispatchEvents, handler: F) -> &mut Self
@@ -87,93 +122,167 @@ impl Client {
         self
     }
 
-    pub async fn login(&mut self) -> anyhow::Result<()> {
-        // Initial IDENTIFY.
-        let gateway = gateway::ws::connect(&self.token, self.intents.clone()).await?;
-        log!(
-            "OK",
-            "Client authenticated (session_id={})",
-            gateway.session_id
-        );
-        self.gateway = Some(gateway);
-        log!("CLI", "Client is running. use Ctrl+C to exit.");
+    /// Handles every dispatch as a typed [`Event`], decoded once per dispatch:
+    /// `client.on_event(|ctx, ev| async move { if let Event::MessageCreate(m) = &*ev { .. } })`.
+    pub fn on_event<F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        F: Send + Sync + 'static + Fn(Ctx, Arc<Event>) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.router.on_event(handler);
+        self
+    }
+
+    /// Every dispatch as a [`Stream`](futures_util::Stream) of
+    /// `(shard, event)`; subscribe before [`Client::login`] and consume it from
+    /// another task.
+    pub fn events(&mut self) -> EventStream {
+        self.router.event_stream(None)
+    }
+
+    /// Like [`Client::events`], only yielding dispatches of `kind`.
+    pub fn events_of(&mut self, kind: GatewayDispatchEvents) -> EventStream {
+        self.router.event_stream(Some(kind))
+    }
+
+    /// Handles the dispatch named `name` with its raw JSON, including events
+    /// newer than this library.
+    pub fn on_raw<F, Fut>(&mut self, name: &str, handler: F) -> &mut Self
+    where
+        F: Send + Sync + 'static + Fn(Ctx, Box<RawValue>) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.router.on_raw(name, handler);
+        self
+    }
 
-        loop {
-            let gw = self.gateway.as_mut().expect("gateway must exist");
+    /// Handles dispatches nothing else handled; see [`Ctx::raw`].
+    pub fn on_unknown<F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        F: Send + Sync + 'static + Fn(Ctx) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.router.on_unknown(handler);
+        self
+    }
+
+    /// Feeds a recording through the registered handlers without connecting;
+    /// returns how many dispatches were handled.
+    pub async fn replay(&self, replay: &Replay) -> usize {
+        replay.run(&self.router, &self.ctx).await
+    }
+
+    /// Stops [`Client::login`] from another task; see [`ShutdownHandle`].
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Runs every shard until the [`ShutdownHandle`] fires (or Ctrl+C, unless
+    /// disabled on the builder).
+    ///
+    /// Returns [`GatewayError::Fatal`] when a shard is closed with a
+    /// non-recoverable code (e.g. `AuthenticationFailed`, `DisallowedIntents`).
+    pub async fn login(&mut self) -> Result<(), GatewayError> {
+        let mut manager = ShardManager::new(
+            self.identify.clone(),
+            Arc::clone(self.ctx.inner.http()),
+            self.shards.clone(),
+            Arc::clone(self.ctx.shards()),
+        );
+        if let Some(queue) = &self.identify_queue {
+            manager.identify_queue(Arc::clone(queue));
+        }
+        manager
+            .encoding(self.encoding)
+            .transport_compression(self.transport_compression)
+            .reconnect_policy(self.reconnect.clone())
+            .event_capacity(self.event_capacity)
+            .event_policies(self.event_policies.clone())
+            .event_stats(Arc::clone(self.ctx.inner.events()));
+        if let Some(url) = &self.gateway_url {
+            manager.gateway_url(url.clone());
+        }
+        if let Some(store) = &self.session_store {
+            manager.session_store(Arc::clone(store));
+        }
+        if let Some(recorder) = &self.recorder {
+            manager.recorder(Arc::clone(recorder));
+        }
+        if let Some(proxy) = &self.proxy {
+            manager.proxy(proxy.clone());
+        }
+        if let Some(presence) = &self.presence {
+            manager.presence(presence.clone());
+        }
+        let mut events_rx = manager.start().await?;
+        if self.ctrl_c {
+            log!("CLI", "Client is running. use Ctrl+C to exit.");
+        } else {
+            log!("CLI", "Client is running.");
+        }
 
+        let mode = loop {
             tokio::select! {
-                maybe = gw.events_rx.recv() => {
-                    match maybe {
-                        Some(ev) => {
-                            let ev = Arc::new(ev);
-                            self.router.dispatch(&self.ctx, ev).await;
-                        }
-                        None => {
-                            log!("WARN", "events_rx closed — attempting reconnection…");
-
-                            let old_gw = self.gateway.take().expect("gateway must exist");
-                            let session_id = old_gw.session_id.clone();
-                            let resume_gateway_url = old_gw.resume_gateway_url.clone();
-                            let last_seq = old_gw.last_seq_rx.borrow().clone();
-
-                            let mut delay = std::time::Duration::from_secs(1);
-                            use crate::gateway::ws::ResumeError;
-
-                            loop {
-                                match gateway::ws::resume(&self.token, &session_id, &resume_gateway_url, last_seq).await {
-                                    Ok(new_gw) => {
-                                        log!("OK", "RESUMED successfully");
-                                        self.gateway = Some(new_gw);
-                                        break;
-                                    }
-                                    Err(ResumeError::InvalidSession { resumable: false }) => {
-                                        log!("WARN", "Session not resumable — fresh IDENTIFY…");
-                                        let ms: u64 = jitter(1000, 5000);
-                                        tokio::time::sleep(std::time::Duration::from_millis(ms)).await;
-
-                                        match gateway::ws::connect(&self.token, self.intents.clone()).await {
-                                            Ok(new_gw) => {
-                                                log!("OK", "Re-IDENTIFY successful (session_id={})", new_gw.session_id);
-                                                self.gateway = Some(new_gw);
-                                                break;
-                                            }
-                                            Err(e2) => {
-                                                log!("ERR", "Re-connect failed: {e2}. Retrying in {:?}…", delay);
-                                                tokio::time::sleep(delay).await;
-                                                delay = std::cmp::min(delay * 2, std::time::Duration::from_secs(60));
-                                            }
-                                        }
-                                    }
-                                    Err(ResumeError::InvalidSession { resumable: true }) => {
-                                        log!("WARN", "Session temporarily invalid; retrying RESUME…");
-                                        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-                                    }
-                                    Err(ResumeError::Transport(err)) => {
-                                        log!("WARN", "RESUME transport error: {err}. Retrying in {:?}…", delay);
-                                        tokio::time::sleep(delay).await;
-                                        delay = std::cmp::min(delay * 2, std::time::Duration::from_secs(60));
-                                    }
-                                }
-                            }
+                // Fatal errors first: the last shard to fail also ends the event stream.
+                biased;
+
+                (shard_id, err) = manager.fatal() => {
+                    log!("ERR", "[shard {shard_id}] fatal gateway error: {err}");
+                    manager.shutdown(CloseMode::Normal);
+                    manager.join().await;
+                    return Err(err);
+                }
+
+                maybe = events_rx.recv() => {
+                    let Some((shard_id, ev)) = maybe else {
+                        if let Some((shard_id, err)) = manager.try_fatal() {
+                            log!("ERR", "[shard {shard_id}] fatal gateway error: {err}");
+                            manager.join().await;
+                            return Err(err);
                         }
-                    }
+                        log!("WARN", "all shards stopped");
+                        return Ok(());
+                    };
+                    let ev = Arc::new(ev);
+                    self.router.dispatch(&self.ctx.for_shard(shard_id), ev).await;
                 }
 
-                _ = tokio::signal::ctrl_c() => {
+                mode = self.shutdown.requested() => break mode,
+
+                _ = tokio::signal::ctrl_c(), if self.ctrl_c => {
                     log!("CLI", "Keyboard Interrupt: Exiting");
-                    if let Some(gw) = self.gateway.as_ref() {
-                        use tokio_tungstenite::tungstenite::protocol::{CloseFrame, frame::coding::CloseCode};
-                        use tokio_tungstenite::tungstenite::Message;
-                        let _ = gw.writer_tx.send(Message::Close(Some(CloseFrame {
-                            code: CloseCode::Normal,
-                            reason: "shutdown".into(),
-                        })));
-                    }
-                    break;
+                    // Keep sessions resumable when they are saved for the next run.
+                    break match self.session_store {
+                        Some(_) => CloseMode::Resumable,
+                        None => CloseMode::Normal,
+                    };
                 }
             }
+        };
+
+        log!("CLI", "shutting down ({mode:?})");
+        manager.shutdown(mode);
+        // Shards stop sending once closed; hand what is queued to the handlers.
+        let drain = async {
+            while let Some((shard_id, ev)) = events_rx.recv().await {
+                let ev = Arc::new(ev);
+                self.router
+                    .dispatch(&self.ctx.for_shard(shard_id), ev)
+                    .await;
+            }
+        };
+        if tokio::time::timeout(self.shutdown_timeout, drain)
+            .await
+            .is_err()
+        {
+            log!(
+                "WARN",
+                "handlers still running after {:?}; dropping {} queued dispatch(es)",
+                self.shutdown_timeout,
+                self.ctx.event_stats().queued()
+            );
         }
-
+        manager.join().await;
         Ok(())
     }
 }
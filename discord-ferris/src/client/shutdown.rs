@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+use crate::gateway::shard::CloseMode;
+
+/// Stops a running [`Client`](super::Client) from anywhere: a command, a test
+/// or a supervisor task.
+///
+/// [`Client::login`](super::Client::login) closes every shard, lets queued
+/// dispatches reach their handlers (up to the builder's `shutdown_timeout`)
+/// and returns.
+#[derive(Clone, Debug)]
+pub struct ShutdownHandle {
+    tx: Arc<watch::Sender<Option<CloseMode>>>,
+}
+
+impl ShutdownHandle {
+    pub(crate) fn new() -> Self {
+        Self {
+            tx: Arc::new(watch::channel(None).0),
+        }
+    }
+
+    /// Closes every shard with code 1000, ending their sessions.
+    pub fn shutdown(&self) {
+        self.shutdown_with(CloseMode::Normal);
+    }
+
+    /// Closes every shard with code 4000 so their sessions can be resumed.
+    pub fn shutdown_resumable(&self) {
+        self.shutdown_with(CloseMode::Resumable);
+    }
+
+    /// Requests a shutdown; later calls keep the first mode.
+    pub fn shutdown_with(&self, mode: CloseMode) {
+        self.tx.send_if_modified(|current| {
+            if current.is_some() {
+                return false;
+            }
+            *current = Some(mode);
+            true
+        });
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.tx.borrow().is_some()
+    }
+
+    /// Resolves with the requested mode once shutdown was asked for.
+    pub(crate) async fn requested(&self) -> CloseMode {
+        let mut rx = self.tx.subscribe();
+        let mode = rx
+            .wait_for(Option::is_some)
+            .await
+            .expect("the handle owns the sender");
+        mode.unwrap_or_default()
+    }
+}
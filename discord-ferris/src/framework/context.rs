@@ -1,10 +1,16 @@
 use std::fmt;
 use std::sync::Arc;
+use std::time::Duration;
 
+use crate::gateway::members::{GuildMembers, MemberFilter};
+use crate::gateway::pipeline::EventStats;
+use crate::gateway::shard::{ShardId, ShardRegistry};
 use crate::http::Http;
 use crate::models::gateway::{
-    GatewayDispatch, GatewayDispatchEvents as GwEvt, GatewayReadyDispatchData,
+    GatewayActivityUpdateData, GatewayDispatch, GatewayDispatchEvents as GwEvt,
+    GatewayReadyDispatchData,
 };
+use crate::models::payloads::PresenceUpdateStatus;
 use serde::Deserialize;
 use serde::de::DeserializeOwned;
 use serde_json::{Value, value::RawValue};
@@ -12,21 +18,37 @@ use serde_json::{Value, value::RawValue};
 #[derive(Clone)]
 pub struct Context {
     pub http: Arc<Http>,
+    pub shards: Arc<ShardRegistry>,
+    pub events: Arc<EventStats>,
 }
 impl Context {
     #[inline]
     pub fn new(http: Arc<Http>) -> Self {
-        Self { http }
+        Self {
+            http,
+            shards: Arc::default(),
+            events: Arc::default(),
+        }
     }
     #[inline]
     pub fn http(&self) -> &Arc<Http> {
         &self.http
     }
+    #[inline]
+    pub fn shards(&self) -> &Arc<ShardRegistry> {
+        &self.shards
+    }
+    #[inline]
+    pub fn events(&self) -> &Arc<EventStats> {
+        &self.events
+    }
 }
 impl fmt::Debug for Context {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Context")
             .field("http", &"Http { redacted }")
+            .field("shards", &self.shards)
+            .field("events", &self.events)
             .finish()
     }
 }
@@ -34,25 +56,98 @@ impl fmt::Debug for Context {
 #[derive(Clone, Debug)]
 pub struct Ctx {
     pub(crate) inner: Arc<Context>,
+    pub(crate) shard_id: ShardId,
     pub(crate) event: Option<Arc<GatewayDispatch<Box<RawValue>>>>,
 }
 impl Ctx {
     #[inline]
     pub fn new(inner: Arc<Context>) -> Self {
-        Self { inner, event: None }
+        Self {
+            inner,
+            shard_id: 0,
+            event: None,
+        }
     }
     #[inline]
-    pub(crate) fn with_event(inner: Arc<Context>, ev: Arc<GatewayDispatch<Box<RawValue>>>) -> Self {
+    pub(crate) fn for_shard(&self, shard_id: ShardId) -> Self {
         Self {
-            inner,
+            inner: Arc::clone(&self.inner),
+            shard_id,
+            event: None,
+        }
+    }
+    #[inline]
+    pub(crate) fn with_event(&self, ev: Arc<GatewayDispatch<Box<RawValue>>>) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+            shard_id: self.shard_id,
             event: Some(ev),
         }
     }
 
+    /// Shard that received the current dispatch.
+    #[inline]
+    pub fn shard_id(&self) -> ShardId {
+        self.shard_id
+    }
+
+    /// Heartbeat round-trip time of the current shard.
+    #[inline]
+    pub fn latency(&self) -> Option<Duration> {
+        self.inner.shards.get(self.shard_id)?.latency()
+    }
+
+    /// Every shard of this process (latency and reconnect stats).
+    #[inline]
+    pub fn shards(&self) -> &Arc<ShardRegistry> {
+        self.inner.shards()
+    }
+
+    /// Event queue depth and dispatches dropped by its overflow policies.
+    #[inline]
+    pub fn event_stats(&self) -> &Arc<EventStats> {
+        self.inner.events()
+    }
+
+    /// Sets the bot's status and activity on the current shard (op 3).
+    /// Use [`ShardRegistry::set_presence`] to update every shard.
+    pub fn set_presence(
+        &self,
+        status: PresenceUpdateStatus,
+        activity: Option<GatewayActivityUpdateData>,
+    ) -> anyhow::Result<()> {
+        let Some(shard) = self.inner.shards.get(self.shard_id) else {
+            anyhow::bail!("shard {} is not running", self.shard_id);
+        };
+        shard.set_presence(status, activity)
+    }
+
+    /// Requests guild members over the gateway (op 8) and waits for every
+    /// chunk of the reply. Sent on the shard that owns `guild_id`.
+    pub async fn request_guild_members(
+        &self,
+        guild_id: impl Into<String>,
+        filter: MemberFilter,
+        presences: bool,
+    ) -> anyhow::Result<GuildMembers> {
+        let guild_id = guild_id.into();
+        let Some(shard) = self.inner.shards.for_guild(&guild_id) else {
+            anyhow::bail!("no running shard handles guild {guild_id}");
+        };
+        shard
+            .request_guild_members(guild_id, filter, presences)
+            .await
+    }
+
     #[inline]
     pub fn event_name(&self) -> Option<GwEvt> {
         self.event.as_ref().map(|ev| ev.t.clone())
     }
+    /// Undecoded `d` of the current dispatch.
+    #[inline]
+    pub fn raw(&self) -> Option<&RawValue> {
+        self.event.as_ref().map(|ev| &*ev.d)
+    }
     #[inline]
     pub fn is(&self, event: GwEvt) -> bool {
         matches!(self.event_name(), Some(t) if t == event)
@@ -111,14 +206,16 @@ impl Ctx {
         self.inner
             .http
             .send_message(&m.channel_id, content, Some(&m.id), false)
-            .await
+            .await?;
+        Ok(())
     }
     #[inline]
     pub async fn say(&self, channel_id: &str, content: &str) -> anyhow::Result<()> {
         self.inner
             .http
             .send_message(channel_id, content, None, false)
-            .await
+            .await?;
+        Ok(())
     }
     #[inline]
     pub fn ready(&self) -> Option<GatewayReadyDispatchData> {
@@ -0,0 +1,198 @@
+//! Dispatches decoded once into a typed [`Event`].
+//!
+//! [`Router::on_event`](crate::framework::router::Router::on_event) handlers
+//! share one `Arc<Event>` per dispatch instead of each decoding the raw `d`.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_util::Stream;
+use serde_json::value::RawValue;
+use tokio::sync::mpsc;
+
+use crate::gateway::shard::ShardId;
+
+use crate::models::gateway::GatewayDispatchEvents as GwEvt;
+use crate::models::gateway::{
+    GatewayApplicationCommandPermissionsUpdateDispatchData,
+    GatewayAutoModerationActionExecutionDispatchData, GatewayAutoModerationRuleCreateDispatchData,
+    GatewayAutoModerationRuleDeleteDispatchData, GatewayAutoModerationRuleUpdateDispatchData,
+    GatewayChannelCreateDispatchData, GatewayChannelDeleteDispatchData,
+    GatewayChannelPinsUpdateDispatchData, GatewayChannelUpdateDispatchData,
+    GatewayEntitlementCreateDispatchData, GatewayEntitlementDeleteDispatchData,
+    GatewayEntitlementUpdateDispatchData, GatewayGuildAuditLogEntryCreateDispatchData,
+    GatewayGuildBanAddDispatchData, GatewayGuildBanRemoveDispatchData,
+    GatewayGuildCreateDispatchData, GatewayGuildDeleteDispatchData,
+    GatewayGuildEmojisUpdateDispatchData, GatewayGuildIntegrationsUpdateDispatchData,
+    GatewayGuildMemberAddDispatchData, GatewayGuildMemberRemoveDispatchData,
+    GatewayGuildMemberUpdateDispatchData, GatewayGuildMembersChunkDispatchData,
+    GatewayGuildRoleCreateDispatchData, GatewayGuildRoleDeleteDispatchData,
+    GatewayGuildRoleUpdateDispatchData, GatewayGuildScheduledEventCreateDispatchData,
+    GatewayGuildScheduledEventDeleteDispatchData, GatewayGuildScheduledEventUpdateDispatchData,
+    GatewayGuildScheduledEventUserAddDispatchData,
+    GatewayGuildScheduledEventUserRemoveDispatchData,
+    GatewayGuildSoundboardSoundCreateDispatchData, GatewayGuildSoundboardSoundDeleteDispatchData,
+    GatewayGuildSoundboardSoundUpdateDispatchData, GatewayGuildSoundboardSoundsUpdateDispatchData,
+    GatewayGuildStickersUpdateDispatchData, GatewayGuildUpdateDispatchData,
+    GatewayIntegrationCreateDispatchData, GatewayIntegrationDeleteDispatchData,
+    GatewayIntegrationUpdateDispatchData, GatewayInteractionCreateDispatchData,
+    GatewayInviteCreateDispatchData, GatewayInviteDeleteDispatchData,
+    GatewayMessageCreateDispatchData, GatewayMessageDeleteBulkDispatchData,
+    GatewayMessageDeleteDispatchData, GatewayMessagePollVoteDispatchData,
+    GatewayMessageReactionAddDispatchData, GatewayMessageReactionRemoveAllDispatchData,
+    GatewayMessageReactionRemoveDispatchData, GatewayMessageReactionRemoveEmojiDispatchData,
+    GatewayMessageUpdateDispatchData, GatewayPresenceUpdateDispatchData, GatewayReadyDispatchData,
+    GatewaySoundboardSoundsDispatchData, GatewayStageInstanceCreateDispatchData,
+    GatewayStageInstanceDeleteDispatchData, GatewayStageInstanceUpdateDispatchData,
+    GatewaySubscriptionCreateDispatchData, GatewaySubscriptionDeleteDispatchData,
+    GatewaySubscriptionUpdateDispatchData, GatewayThreadCreateDispatchData,
+    GatewayThreadDeleteDispatchData, GatewayThreadListSyncDispatchData,
+    GatewayThreadMemberUpdateDispatchData, GatewayThreadMembersUpdateDispatchData,
+    GatewayThreadUpdateDispatchData, GatewayTypingStartDispatchData, GatewayUserUpdateDispatchData,
+    GatewayVoiceChannelEffectSendDispatchData, GatewayVoiceServerUpdateDispatchData,
+    GatewayVoiceStateUpdateDispatchData, GatewayWebhooksUpdateDispatchData,
+};
+
+macro_rules! events {
+    ($( $variant:ident => $ty:ty ),* $(,)?) => {
+        /// A dispatch with its payload decoded, one variant per
+        /// [`GatewayDispatchEvents`](crate::models::gateway::GatewayDispatchEvents).
+        // Always shared behind an `Arc`, so the size of the largest payload is
+        // paid once per dispatch.
+        #[allow(clippy::large_enum_variant)]
+        #[derive(Clone, Debug)]
+        pub enum Event {
+            $( $variant($ty), )*
+            /// A dispatch this version does not know, with its raw `d`.
+            Unknown { name: String, data: Box<RawValue> },
+        }
+
+        impl Event {
+            /// Decodes `d` as the payload of `kind`.
+            pub fn decode(kind: &GwEvt, d: &RawValue) -> serde_json::Result<Self> {
+                Ok(match kind {
+                    $( GwEvt::$variant => Self::$variant(serde_json::from_str(d.get())?), )*
+                    GwEvt::Unknown(name) => Self::Unknown {
+                        name: name.clone(),
+                        data: d.to_owned(),
+                    },
+                })
+            }
+
+            /// Which dispatch this is.
+            pub fn kind(&self) -> GwEvt {
+                match self {
+                    $( Self::$variant(_) => GwEvt::$variant, )*
+                    Self::Unknown { name, .. } => GwEvt::Unknown(name.clone()),
+                }
+            }
+        }
+    };
+}
+
+events! {
+    ApplicationCommandPermissionsUpdate => GatewayApplicationCommandPermissionsUpdateDispatchData,
+    AutoModerationActionExecution => GatewayAutoModerationActionExecutionDispatchData,
+    AutoModerationRuleCreate => GatewayAutoModerationRuleCreateDispatchData,
+    AutoModerationRuleDelete => GatewayAutoModerationRuleDeleteDispatchData,
+    AutoModerationRuleUpdate => GatewayAutoModerationRuleUpdateDispatchData,
+    ChannelCreate => GatewayChannelCreateDispatchData,
+    ChannelDelete => GatewayChannelDeleteDispatchData,
+    ChannelPinsUpdate => GatewayChannelPinsUpdateDispatchData,
+    ChannelUpdate => GatewayChannelUpdateDispatchData,
+    EntitlementCreate => GatewayEntitlementCreateDispatchData,
+    EntitlementDelete => GatewayEntitlementDeleteDispatchData,
+    EntitlementUpdate => GatewayEntitlementUpdateDispatchData,
+    GuildAuditLogEntryCreate => GatewayGuildAuditLogEntryCreateDispatchData,
+    GuildBanAdd => GatewayGuildBanAddDispatchData,
+    GuildBanRemove => GatewayGuildBanRemoveDispatchData,
+    GuildCreate => GatewayGuildCreateDispatchData,
+    GuildDelete => GatewayGuildDeleteDispatchData,
+    GuildEmojisUpdate => GatewayGuildEmojisUpdateDispatchData,
+    GuildIntegrationsUpdate => GatewayGuildIntegrationsUpdateDispatchData,
+    GuildMemberAdd => GatewayGuildMemberAddDispatchData,
+    GuildMemberRemove => GatewayGuildMemberRemoveDispatchData,
+    GuildMembersChunk => GatewayGuildMembersChunkDispatchData,
+    GuildMemberUpdate => GatewayGuildMemberUpdateDispatchData,
+    GuildRoleCreate => GatewayGuildRoleCreateDispatchData,
+    GuildRoleDelete => GatewayGuildRoleDeleteDispatchData,
+    GuildRoleUpdate => GatewayGuildRoleUpdateDispatchData,
+    GuildScheduledEventCreate => GatewayGuildScheduledEventCreateDispatchData,
+    GuildScheduledEventDelete => GatewayGuildScheduledEventDeleteDispatchData,
+    GuildScheduledEventUpdate => GatewayGuildScheduledEventUpdateDispatchData,
+    GuildScheduledEventUserAdd => GatewayGuildScheduledEventUserAddDispatchData,
+    GuildScheduledEventUserRemove => GatewayGuildScheduledEventUserRemoveDispatchData,
+    GuildSoundboardSoundCreate => GatewayGuildSoundboardSoundCreateDispatchData,
+    GuildSoundboardSoundDelete => GatewayGuildSoundboardSoundDeleteDispatchData,
+    GuildSoundboardSoundsUpdate => GatewayGuildSoundboardSoundsUpdateDispatchData,
+    GuildSoundboardSoundUpdate => GatewayGuildSoundboardSoundUpdateDispatchData,
+    SoundboardSounds => GatewaySoundboardSoundsDispatchData,
+    GuildStickersUpdate => GatewayGuildStickersUpdateDispatchData,
+    GuildUpdate => GatewayGuildUpdateDispatchData,
+    IntegrationCreate => GatewayIntegrationCreateDispatchData,
+    IntegrationDelete => GatewayIntegrationDeleteDispatchData,
+    IntegrationUpdate => GatewayIntegrationUpdateDispatchData,
+    InteractionCreate => GatewayInteractionCreateDispatchData,
+    InviteCreate => GatewayInviteCreateDispatchData,
+    InviteDelete => GatewayInviteDeleteDispatchData,
+    MessageCreate => GatewayMessageCreateDispatchData,
+    MessageDelete => GatewayMessageDeleteDispatchData,
+    MessageDeleteBulk => GatewayMessageDeleteBulkDispatchData,
+    MessagePollVoteAdd => GatewayMessagePollVoteDispatchData,
+    MessagePollVoteRemove => GatewayMessagePollVoteDispatchData,
+    MessageReactionAdd => GatewayMessageReactionAddDispatchData,
+    MessageReactionRemove => GatewayMessageReactionRemoveDispatchData,
+    MessageReactionRemoveAll => GatewayMessageReactionRemoveAllDispatchData,
+    MessageReactionRemoveEmoji => GatewayMessageReactionRemoveEmojiDispatchData,
+    MessageUpdate => GatewayMessageUpdateDispatchData,
+    PresenceUpdate => GatewayPresenceUpdateDispatchData,
+    Ready => GatewayReadyDispatchData,
+    Resumed => (),
+    StageInstanceCreate => GatewayStageInstanceCreateDispatchData,
+    StageInstanceDelete => GatewayStageInstanceDeleteDispatchData,
+    StageInstanceUpdate => GatewayStageInstanceUpdateDispatchData,
+    SubscriptionCreate => GatewaySubscriptionCreateDispatchData,
+    SubscriptionDelete => GatewaySubscriptionDeleteDispatchData,
+    SubscriptionUpdate => GatewaySubscriptionUpdateDispatchData,
+    ThreadCreate => GatewayThreadCreateDispatchData,
+    ThreadDelete => GatewayThreadDeleteDispatchData,
+    ThreadListSync => GatewayThreadListSyncDispatchData,
+    ThreadMembersUpdate => GatewayThreadMembersUpdateDispatchData,
+    ThreadMemberUpdate => GatewayThreadMemberUpdateDispatchData,
+    ThreadUpdate => GatewayThreadUpdateDispatchData,
+    TypingStart => GatewayTypingStartDispatchData,
+    UserUpdate => GatewayUserUpdateDispatchData,
+    VoiceChannelEffectSend => GatewayVoiceChannelEffectSendDispatchData,
+    VoiceServerUpdate => GatewayVoiceServerUpdateDispatchData,
+    VoiceStateUpdate => GatewayVoiceStateUpdateDispatchData,
+    WebhooksUpdate => GatewayWebhooksUpdateDispatchData,
+}
+
+/// Dispatches of a running client as a [`Stream`], for `StreamExt`
+/// combinators and `select!` loops.
+///
+/// Fed by the event loop of [`Client::login`](crate::client::Client::login);
+/// a slow consumer holds up the loop once [`EVENT_STREAM_CAPACITY`] events
+/// are buffered. Ends once the client is dropped.
+pub struct EventStream {
+    rx: mpsc::Receiver<(ShardId, Arc<Event>)>,
+}
+
+/// Events buffered per [`EventStream`].
+pub const EVENT_STREAM_CAPACITY: usize = 256;
+
+impl EventStream {
+    pub(crate) fn channel() -> (mpsc::Sender<(ShardId, Arc<Event>)>, Self) {
+        let (tx, rx) = mpsc::channel(EVENT_STREAM_CAPACITY);
+        (tx, Self { rx })
+    }
+}
+
+impl Stream for EventStream {
+    type Item = (ShardId, Arc<Event>);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
@@ -1,3 +1,4 @@
 pub mod context;
+pub mod event;
 pub mod events;
 pub mod router;
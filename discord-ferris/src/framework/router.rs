@@ -6,13 +6,14 @@ use std::future::Future;
 use std::marker::PhantomData;
 use std::sync::{Arc, Mutex};
 
-use crate::framework::context::{Context, Ctx};
+use crate::framework::context::Ctx;
+use crate::framework::event::{Event, EventStream};
 use crate::models::gateway::{GatewayDispatch, GatewayDispatchEvents as GwEvt};
 
 #[async_trait]
 pub trait DynHandler: Send + Sync {
     // Keep raw `d` and decode lazily.
-    async fn call(&self, ctx: Arc<Context>, ev: Arc<GatewayDispatch<Box<RawValue>>>);
+    async fn call(&self, base: &Ctx, ev: Arc<GatewayDispatch<Box<RawValue>>>);
     fn event(&self) -> GwEvt;
     fn is_once(&self) -> bool {
         false
@@ -33,13 +34,13 @@ where
     F: Send + Sync + 'static + Fn(Ctx, T) -> Fut,
     Fut: Future<Output = ()> + Send + 'static,
 {
-    async fn call(&self, ctx: Arc<Context>, ev: Arc<GatewayDispatch<Box<RawValue>>>) {
+    async fn call(&self, base: &Ctx, ev: Arc<GatewayDispatch<Box<RawValue>>>) {
         if ev.t != self.event {
             return;
         }
         match serde_json::from_str::<T>(ev.d.get()) {
             Ok(payload) => {
-                let c = Ctx::with_event(Arc::clone(&ctx), Arc::clone(&ev));
+                let c = base.with_event(Arc::clone(&ev));
                 (self.f)(c, payload).await;
             }
             Err(err) => {
@@ -58,7 +59,7 @@ where
 
 #[async_trait]
 pub trait DynAnyHandler: Send + Sync {
-    async fn call(&self, ctx: Arc<Context>, ev: Arc<GatewayDispatch<Box<RawValue>>>);
+    async fn call(&self, base: &Ctx, ev: Arc<GatewayDispatch<Box<RawValue>>>);
 }
 
 #[async_trait]
@@ -67,16 +68,35 @@ where
     F: Send + Sync + 'static + Fn(Ctx) -> Fut,
     Fut: Future<Output = ()> + Send + 'static,
 {
-    async fn call(&self, ctx: Arc<Context>, ev: Arc<GatewayDispatch<Box<RawValue>>>) {
-        let c = Ctx::with_event(Arc::clone(&ctx), Arc::clone(&ev));
+    async fn call(&self, base: &Ctx, ev: Arc<GatewayDispatch<Box<RawValue>>>) {
+        let c = base.with_event(Arc::clone(&ev));
         (self)(c).await;
     }
 }
 
+#[async_trait]
+pub trait DynEventHandler: Send + Sync {
+    async fn call(&self, base: &Ctx, ev: Arc<GatewayDispatch<Box<RawValue>>>, event: Arc<Event>);
+}
+
+#[async_trait]
+impl<F, Fut> DynEventHandler for F
+where
+    F: Send + Sync + 'static + Fn(Ctx, Arc<Event>) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    async fn call(&self, base: &Ctx, ev: Arc<GatewayDispatch<Box<RawValue>>>, event: Arc<Event>) {
+        let c = base.with_event(ev);
+        (self)(c, event).await;
+    }
+}
+
 pub struct Router {
     routes: HashMap<GwEvt, Vec<Arc<dyn DynHandler>>>,
     once_routes: Mutex<HashMap<GwEvt, Vec<Arc<dyn DynHandler>>>>,
     any: Vec<Arc<dyn DynAnyHandler>>,
+    /// Event handlers with the kind they want (`None` for all).
+    events: Vec<(Option<GwEvt>, Arc<dyn DynEventHandler>)>,
     unknown: Vec<Arc<dyn DynAnyHandler>>,
 }
 
@@ -86,6 +106,7 @@ impl Router {
             routes: HashMap::new(),
             once_routes: Mutex::new(HashMap::new()),
             any: vec![],
+            events: vec![],
             unknown: vec![],
         }
     }
@@ -121,6 +142,16 @@ impl Router {
         map.entry(kind).or_default().push(h);
     }
 
+    /// Registers `handler` for the dispatch named `name` (e.g. `"MESSAGE_CREATE"`),
+    /// known or not, with its undecoded `d`.
+    pub fn on_raw<F, Fut>(&mut self, name: &str, handler: F)
+    where
+        F: Send + Sync + 'static + Fn(Ctx, Box<RawValue>) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on::<Box<RawValue>, F, Fut>(GwEvt::from_name(name), handler);
+    }
+
     pub fn on_all<F, Fut>(&mut self, handler: F)
     where
         F: Send + Sync + 'static + Fn(Ctx) -> Fut,
@@ -129,6 +160,35 @@ impl Router {
         self.any.push(Arc::new(handler));
     }
 
+    /// Registers `handler` for every dispatch, decoded into an [`Event`].
+    /// The payload is decoded once and shared by all such handlers.
+    pub fn on_event<F, Fut>(&mut self, handler: F)
+    where
+        F: Send + Sync + 'static + Fn(Ctx, Arc<Event>) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.events.push((None, Arc::new(handler)));
+    }
+
+    /// Streams every dispatch as an [`Event`], or only those of `kind`;
+    /// other kinds are not decoded for it.
+    pub fn event_stream(&mut self, kind: Option<GwEvt>) -> EventStream {
+        let (tx, stream) = EventStream::channel();
+        let handler = move |c: Ctx, event| {
+            let tx = tx.clone();
+            async move {
+                // Dropping the stream only ends the subscription.
+                if !tx.is_closed() {
+                    let _ = tx.send((c.shard_id(), event)).await;
+                }
+            }
+        };
+        self.events.push((kind, Arc::new(handler)));
+        stream
+    }
+
+    /// Runs for dispatches no other handler took, including
+    /// [`Unknown`](GwEvt::Unknown) ones; read `d` with [`Ctx::raw`].
     pub fn on_unknown<F, Fut>(&mut self, handler: F)
     where
         F: Send + Sync + 'static + Fn(Ctx) -> Fut,
@@ -138,21 +198,36 @@ impl Router {
     }
 
     pub async fn dispatch(&self, base: &Ctx, ev: Arc<GatewayDispatch<Box<RawValue>>>) {
-        let ctx_arc = Arc::clone(&base.inner);
-
         // on_all
         for h in &self.any {
-            h.call(Arc::clone(&ctx_arc), Arc::clone(&ev)).await;
+            h.call(base, Arc::clone(&ev)).await;
         }
 
         let kind = ev.t.clone();
+        let mut had_handlers = false;
+
+        // --- typed events, decoded once for all that want this kind
+        let wants = |want: &Option<GwEvt>| want.as_ref().is_none_or(|want| *want == kind);
+        if self.events.iter().any(|(want, _)| wants(want)) {
+            match Event::decode(&kind, &ev.d) {
+                Ok(event) => {
+                    let event = Arc::new(event);
+                    had_handlers = true;
+                    for (_, h) in self.events.iter().filter(|(want, _)| wants(want)) {
+                        h.call(base, Arc::clone(&ev), Arc::clone(&event)).await;
+                    }
+                }
+                Err(err) => {
+                    crate::log!("WARN", "decode failed for {:?}: {}", kind, err);
+                }
+            }
+        }
 
         // --- once handlers
-        let mut had_handlers = false;
         if let Some(list) = self.once_routes.lock().unwrap().remove(&kind) {
             had_handlers |= !list.is_empty();
             for h in list {
-                h.call(Arc::clone(&ctx_arc), Arc::clone(&ev)).await;
+                h.call(base, Arc::clone(&ev)).await;
             }
         }
 
@@ -160,22 +235,19 @@ impl Router {
         if let Some(list) = self.routes.get(&kind) {
             had_handlers |= !list.is_empty();
             for h in list {
-                h.call(Arc::clone(&ctx_arc), Arc::clone(&ev)).await;
+                h.call(base, Arc::clone(&ev)).await;
             }
         }
 
         use crate::framework::events;
-        let ran_macros = events::dispatch_inventory_raw(
-            Ctx::with_event(Arc::clone(&ctx_arc), Arc::clone(&ev)),
-            kind.clone(),
-            &ev.d,
-        )
-        .await;
+        let ran_macros =
+            events::dispatch_inventory_raw(base.with_event(Arc::clone(&ev)), kind.clone(), &ev.d)
+                .await;
         had_handlers |= ran_macros > 0;
 
         if !had_handlers {
             for h in &self.unknown {
-                h.call(Arc::clone(&ctx_arc), Arc::clone(&ev)).await;
+                h.call(base, Arc::clone(&ev)).await;
             }
         }
     }
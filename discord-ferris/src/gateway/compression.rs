@@ -0,0 +1,134 @@
+//! `compress=zlib-stream` transport compression (cargo feature `zlib-stream`).
+//!
+//! Discord compresses the whole connection as one zlib stream and flushes it
+//! after every payload, so a single inflater must live as long as the socket.
+//! A payload may span several binary frames; it is complete once the buffered
+//! bytes end with the `00 00 ff ff` sync-flush suffix.
+//!
+//! IDENTIFY's `compress` flag selects payload compression instead: each
+//! binary frame is then a complete zlib stream of its own.
+
+use tokio_tungstenite::tungstenite::Bytes;
+
+/// How the binary frames of one connection are compressed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GatewayCompression {
+    /// Frames are sent as is.
+    #[default]
+    None,
+    /// The whole connection is one zlib stream (`compress=zlib-stream`).
+    Transport,
+    /// Every frame is a zlib stream of its own (IDENTIFY's `compress`).
+    Payload,
+}
+
+impl GatewayCompression {
+    /// Payload compression wins when both are asked for; without the
+    /// `zlib-stream` feature nothing is compressed.
+    pub fn new(transport: bool, payload: bool) -> Self {
+        if !cfg!(feature = "zlib-stream") {
+            GatewayCompression::None
+        } else if payload {
+            GatewayCompression::Payload
+        } else if transport {
+            GatewayCompression::Transport
+        } else {
+            GatewayCompression::None
+        }
+    }
+
+    /// Query parameter appended to the gateway URL.
+    pub(crate) fn query(&self) -> &'static str {
+        match self {
+            GatewayCompression::Transport => "&compress=zlib-stream",
+            GatewayCompression::None | GatewayCompression::Payload => "",
+        }
+    }
+}
+
+#[cfg(feature = "zlib-stream")]
+const ZLIB_SUFFIX: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// Per-connection inflater for binary frames.
+///
+/// Uncompressed binary frames (ETF) pass through unchanged.
+pub struct Inflater {
+    #[cfg(feature = "zlib-stream")]
+    compression: GatewayCompression,
+    #[cfg(feature = "zlib-stream")]
+    zlib: flate2::Decompress,
+    #[cfg(feature = "zlib-stream")]
+    buffer: Vec<u8>,
+}
+
+impl Inflater {
+    pub fn new(#[allow(unused_variables)] compression: GatewayCompression) -> Self {
+        Self {
+            #[cfg(feature = "zlib-stream")]
+            compression,
+            #[cfg(feature = "zlib-stream")]
+            zlib: flate2::Decompress::new(true),
+            #[cfg(feature = "zlib-stream")]
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Buffers a binary frame; returns the inflated payload once complete.
+    #[cfg(feature = "zlib-stream")]
+    pub fn push(&mut self, frame: Bytes) -> anyhow::Result<Option<Bytes>> {
+        use flate2::FlushDecompress;
+
+        match self.compression {
+            GatewayCompression::None => return Ok(Some(frame)),
+            GatewayCompression::Payload => return inflate_payload(&frame).map(Some),
+            GatewayCompression::Transport => {}
+        }
+
+        self.buffer.extend_from_slice(&frame);
+        if !self.buffer.ends_with(&ZLIB_SUFFIX) {
+            return Ok(None);
+        }
+
+        let mut out = Vec::with_capacity(self.buffer.len() * 4);
+        let mut offset = 0;
+        loop {
+            if out.len() == out.capacity() {
+                out.reserve(self.buffer.len().max(1024) * 2);
+            }
+            let before = self.zlib.total_in();
+            self.zlib
+                .decompress_vec(&self.buffer[offset..], &mut out, FlushDecompress::Sync)?;
+            offset += (self.zlib.total_in() - before) as usize;
+            // Done once every input byte is consumed and the output has room left
+            // (a full output buffer may still hold pending data).
+            if offset >= self.buffer.len() && out.len() < out.capacity() {
+                break;
+            }
+        }
+        self.buffer.clear();
+
+        Ok(Some(Bytes::from(out)))
+    }
+
+    /// Binary frames pass through without the `zlib-stream` feature.
+    #[cfg(not(feature = "zlib-stream"))]
+    pub fn push(&mut self, frame: Bytes) -> anyhow::Result<Option<Bytes>> {
+        Ok(Some(frame))
+    }
+}
+
+impl Default for Inflater {
+    fn default() -> Self {
+        Self::new(GatewayCompression::default())
+    }
+}
+
+/// Inflates one payload-compressed frame.
+#[cfg(feature = "zlib-stream")]
+fn inflate_payload(frame: &[u8]) -> anyhow::Result<Bytes> {
+    use std::io::Read;
+
+    let mut out = Vec::with_capacity(frame.len() * 4);
+    flate2::read::ZlibDecoder::new(frame).read_to_end(&mut out)?;
+    Ok(Bytes::from(out))
+}
@@ -0,0 +1,147 @@
+//! Gateway payload encoding (`encoding=json` or `encoding=etf`).
+//!
+//! Either way, dispatch `d` is handed to the router as JSON [`RawValue`]; ETF
+//! payloads are transcoded once on receive (see [`crate::gateway::etf`]).
+
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::gateway::etf;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GatewayEncoding {
+    #[default]
+    Json,
+    /// Erlang External Term Format; smaller frames, snowflakes as integers.
+    Etf,
+}
+
+/// A received gateway payload.
+pub(crate) struct Frame {
+    pub op: i64,
+    pub s: Option<i64>,
+    pub t: Option<String>,
+    pub d: Option<Box<RawValue>>,
+}
+
+#[derive(Deserialize)]
+struct FrameBorrowed<'a> {
+    op: i64,
+    #[serde(default)]
+    s: Option<i64>,
+    #[serde(default)]
+    t: Option<&'a str>,
+    /// Borrowed raw payload slice.
+    #[serde(borrow)]
+    d: Option<&'a RawValue>,
+}
+
+#[derive(Deserialize)]
+struct EtfFrame {
+    op: i64,
+    #[serde(default)]
+    s: Option<i64>,
+    #[serde(default)]
+    t: Option<String>,
+    #[serde(default)]
+    d: Option<serde_json::Value>,
+}
+
+impl GatewayEncoding {
+    /// Value of the `encoding` query parameter.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GatewayEncoding::Json => "json",
+            GatewayEncoding::Etf => "etf",
+        }
+    }
+
+    /// Encodes an outgoing payload as a text (JSON) or binary (ETF) frame.
+    pub(crate) fn encode<T: Serialize>(&self, payload: &T) -> anyhow::Result<Message> {
+        Ok(match self {
+            GatewayEncoding::Json => Message::Text(serde_json::to_string(payload)?.into()),
+            GatewayEncoding::Etf => Message::Binary(etf::to_vec(payload)?.into()),
+        })
+    }
+
+    /// Decodes a complete (already inflated) payload.
+    pub(crate) fn decode(&self, bytes: &[u8]) -> anyhow::Result<Frame> {
+        match self {
+            GatewayEncoding::Json => {
+                let f = serde_json::from_slice::<FrameBorrowed>(bytes)?;
+                Ok(Frame {
+                    op: f.op,
+                    s: f.s,
+                    t: f.t.map(str::to_owned),
+                    d: f.d.map(RawValue::to_owned),
+                })
+            }
+            GatewayEncoding::Etf => {
+                let f = etf::from_slice::<EtfFrame>(bytes)?;
+                let d = match f.d {
+                    Some(d) => Some(serde_json::value::to_raw_value(&d)?),
+                    None => None,
+                };
+                Ok(Frame {
+                    op: f.op,
+                    s: f.s,
+                    t: f.t,
+                    d,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::framework::event::Event;
+    use crate::models::gateway::{GatewayDispatch, GatewayDispatchEvents, GatewayOpcodes};
+
+    #[test]
+    fn etf_big_integers_decode_into_models() {
+        // Snowflakes and millisecond timestamps both arrive as SMALL_BIG_EXT.
+        let payload = json!({
+            "op": 0,
+            "s": 7,
+            "t": "PRESENCE_UPDATE",
+            "d": {
+                "user": { "id": 1111111111111111111u64 },
+                "guild_id": 222222222222222222u64,
+                "status": "online",
+                "activities": [{
+                    "id": "custom",
+                    "name": "Ferris",
+                    "type": 0,
+                    "created_at": 1700000000000u64,
+                    "timestamps": { "start": 1700000000000u64 },
+                }],
+            },
+        });
+        let bytes = etf::to_vec(&payload).unwrap();
+        let frame = GatewayEncoding::Etf.decode(&bytes).unwrap();
+        let dispatch = GatewayDispatch {
+            op: GatewayOpcodes::Dispatch,
+            t: GatewayDispatchEvents::from_name(frame.t.as_deref().unwrap()),
+            s: frame.s.unwrap(),
+            d: frame.d.unwrap(),
+        };
+
+        let Event::PresenceUpdate(presence) = Event::decode(&dispatch.t, &dispatch.d).unwrap()
+        else {
+            panic!("not a presence update");
+        };
+        assert_eq!(presence.base.user.id, "1111111111111111111");
+        assert_eq!(presence.guild_id, "222222222222222222");
+        let activity = &presence.base.activities.unwrap()[0];
+        assert_eq!(activity.created_at, 1700000000000);
+        assert_eq!(
+            activity.timestamps.as_ref().unwrap().start,
+            Some(1700000000000)
+        );
+    }
+}
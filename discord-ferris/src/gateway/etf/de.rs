@@ -0,0 +1,490 @@
+use std::borrow::Cow;
+
+use serde::Deserialize;
+use serde::de::value::SeqDeserializer;
+use serde::de::{
+    self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess,
+    Visitor,
+};
+
+use super::*;
+
+/// Deserializes an ETF term (with its leading version byte) from `bytes`.
+pub fn from_slice<'de, T: Deserialize<'de>>(bytes: &'de [u8]) -> Result<T> {
+    let mut de = Deserializer::new(bytes)?;
+    let value = T::deserialize(&mut de)?;
+    de.end()?;
+    Ok(value)
+}
+
+pub struct Deserializer<'de> {
+    input: &'de [u8],
+}
+
+impl<'de> Deserializer<'de> {
+    /// Expects `bytes` to start with the version byte (131).
+    pub fn new(bytes: &'de [u8]) -> Result<Self> {
+        let mut de = Self { input: bytes };
+        match de.read_u8()? {
+            VERSION => Ok(de),
+            v => Err(Error::Version(v)),
+        }
+    }
+
+    /// Fails if anything follows the decoded term.
+    pub fn end(&self) -> Result<()> {
+        if self.input.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::TrailingBytes)
+        }
+    }
+
+    fn peek(&self) -> Result<u8> {
+        self.input.first().copied().ok_or(Error::Eof)
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'de [u8]> {
+        if self.input.len() < len {
+            return Err(Error::Eof);
+        }
+        let (head, rest) = self.input.split_at(len);
+        self.input = rest;
+        Ok(head)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut out = [0; N];
+        out.copy_from_slice(self.read_bytes(N)?);
+        Ok(out)
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.read_array()?))
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.read_array()?))
+    }
+
+    /// Reads an integer term whose tag was already consumed, as sign and magnitude.
+    fn read_integer(&mut self, tag: u8) -> Result<(bool, u128)> {
+        match tag {
+            SMALL_INTEGER_EXT => Ok((false, self.read_u8()? as u128)),
+            INTEGER_EXT => {
+                let n = i32::from_be_bytes(self.read_array()?);
+                Ok((n < 0, n.unsigned_abs() as u128))
+            }
+            SMALL_BIG_EXT | LARGE_BIG_EXT => {
+                let len = if tag == SMALL_BIG_EXT {
+                    self.read_u8()? as usize
+                } else {
+                    self.read_u32()? as usize
+                };
+                let negative = self.read_u8()? != 0;
+                let digits = self.read_bytes(len)?;
+                let mut magnitude: u128 = 0;
+                for (i, &digit) in digits.iter().enumerate() {
+                    if digit == 0 {
+                        continue;
+                    }
+                    if i >= 16 {
+                        return Err(Error::IntegerOverflow);
+                    }
+                    magnitude |= (digit as u128) << (8 * i);
+                }
+                Ok((negative, magnitude))
+            }
+            tag => Err(Error::UnsupportedTag(tag)),
+        }
+    }
+
+    /// Reads an atom whose tag was already consumed.
+    fn read_atom(&mut self, tag: u8) -> Result<Cow<'de, str>> {
+        let len = match tag {
+            ATOM_EXT | ATOM_UTF8_EXT => self.read_u16()? as usize,
+            _ => self.read_u8()? as usize,
+        };
+        let bytes = self.read_bytes(len)?;
+        match std::str::from_utf8(bytes) {
+            Ok(s) => Ok(Cow::Borrowed(s)),
+            // Legacy ATOM_EXT is Latin-1.
+            Err(_) if tag == ATOM_EXT => Ok(Cow::Owned(bytes.iter().map(|&b| b as char).collect())),
+            Err(e) => Err(de::Error::custom(e)),
+        }
+    }
+
+    fn read_binary(&mut self) -> Result<&'de [u8]> {
+        let len = self.read_u32()? as usize;
+        self.read_bytes(len)
+    }
+
+    /// Consumes the next term if it is the `nil` atom.
+    fn consume_nil(&mut self) -> Result<bool> {
+        let saved = self.input;
+        let tag = self.read_u8()?;
+        if matches!(
+            tag,
+            ATOM_EXT | SMALL_ATOM_EXT | ATOM_UTF8_EXT | SMALL_ATOM_UTF8_EXT
+        ) && self.read_atom(tag)? == "nil"
+        {
+            return Ok(true);
+        }
+        self.input = saved;
+        Ok(false)
+    }
+
+    fn visit_integer<V: Visitor<'de>>(
+        visitor: V,
+        negative: bool,
+        magnitude: u128,
+    ) -> Result<V::Value> {
+        if !negative {
+            return match u64::try_from(magnitude) {
+                Ok(n) => visitor.visit_u64(n),
+                Err(_) => visitor.visit_u128(magnitude),
+            };
+        }
+        match 0i128.checked_sub_unsigned(magnitude) {
+            Some(n) => match i64::try_from(n) {
+                Ok(n) => visitor.visit_i64(n),
+                Err(_) => visitor.visit_i128(n),
+            },
+            None => Err(Error::IntegerOverflow),
+        }
+    }
+
+    fn deserialize_number<V: Visitor<'de>>(&mut self, visitor: V) -> Result<V::Value> {
+        match self.peek()? {
+            tag @ (SMALL_BIG_EXT | LARGE_BIG_EXT) => {
+                self.read_u8()?;
+                let (negative, magnitude) = self.read_integer(tag)?;
+                Self::visit_integer(visitor, negative, magnitude)
+            }
+            _ => de::Deserializer::deserialize_any(self, visitor),
+        }
+    }
+}
+
+/// Largest integer every JSON reader keeps exact (2^53).
+const MAX_SAFE_INTEGER: u128 = 1 << 53;
+
+fn integer_to_string(negative: bool, magnitude: u128) -> String {
+    if negative {
+        format!("-{magnitude}")
+    } else {
+        magnitude.to_string()
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let tag = self.read_u8()?;
+        match tag {
+            SMALL_INTEGER_EXT | INTEGER_EXT => {
+                let (negative, magnitude) = self.read_integer(tag)?;
+                Deserializer::visit_integer(visitor, negative, magnitude)
+            }
+            // Snowflakes lie above 2^53, where JSON numbers stop being exact, so
+            // they stay strings as in JSON payloads; smaller big integers
+            // (e.g. millisecond timestamps) are numbers.
+            SMALL_BIG_EXT | LARGE_BIG_EXT => {
+                let (negative, magnitude) = self.read_integer(tag)?;
+                if magnitude <= MAX_SAFE_INTEGER {
+                    Deserializer::visit_integer(visitor, negative, magnitude)
+                } else {
+                    visitor.visit_string(integer_to_string(negative, magnitude))
+                }
+            }
+            NEW_FLOAT_EXT => visitor.visit_f64(f64::from_be_bytes(self.read_array()?)),
+            FLOAT_EXT => {
+                let raw = self.read_bytes(31)?;
+                let text = std::str::from_utf8(raw).map_err(de::Error::custom)?;
+                let n = text
+                    .trim_end_matches('\0')
+                    .trim()
+                    .parse::<f64>()
+                    .map_err(de::Error::custom)?;
+                visitor.visit_f64(n)
+            }
+            ATOM_EXT | SMALL_ATOM_EXT | ATOM_UTF8_EXT | SMALL_ATOM_UTF8_EXT => {
+                match self.read_atom(tag)? {
+                    Cow::Borrowed("nil") => visitor.visit_unit(),
+                    Cow::Borrowed("true") => visitor.visit_bool(true),
+                    Cow::Borrowed("false") => visitor.visit_bool(false),
+                    Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
+                    Cow::Owned(s) => visitor.visit_string(s),
+                }
+            }
+            BINARY_EXT => {
+                let bytes = self.read_binary()?;
+                match std::str::from_utf8(bytes) {
+                    Ok(s) => visitor.visit_borrowed_str(s),
+                    Err(_) => visitor.visit_borrowed_bytes(bytes),
+                }
+            }
+            STRING_EXT => {
+                // A list of bytes, not text.
+                let len = self.read_u16()? as usize;
+                let bytes = self.read_bytes(len)?;
+                let mut seq = SeqDeserializer::new(bytes.iter().copied());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            NIL_EXT => visitor.visit_seq(Access {
+                de: self,
+                remaining: 0,
+            }),
+            LIST_EXT => {
+                let len = self.read_u32()? as usize;
+                let mut access = Access {
+                    de: &mut *self,
+                    remaining: len,
+                };
+                let value = visitor.visit_seq(&mut access)?;
+                access.end()?;
+                match self.read_u8()? {
+                    NIL_EXT => Ok(value),
+                    _ => Err(Error::ImproperList),
+                }
+            }
+            SMALL_TUPLE_EXT | LARGE_TUPLE_EXT => {
+                let len = if tag == SMALL_TUPLE_EXT {
+                    self.read_u8()? as usize
+                } else {
+                    self.read_u32()? as usize
+                };
+                let mut access = Access {
+                    de: &mut *self,
+                    remaining: len,
+                };
+                let value = visitor.visit_seq(&mut access)?;
+                access.end()?;
+                Ok(value)
+            }
+            MAP_EXT => {
+                let len = self.read_u32()? as usize;
+                let mut access = Access {
+                    de: &mut *self,
+                    remaining: len,
+                };
+                let value = visitor.visit_map(&mut access)?;
+                access.end()?;
+                Ok(value)
+            }
+            tag => Err(Error::UnsupportedTag(tag)),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.consume_nil()? {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.peek()? {
+            tag @ (SMALL_INTEGER_EXT | INTEGER_EXT | SMALL_BIG_EXT | LARGE_BIG_EXT) => {
+                self.read_u8()?;
+                let (negative, magnitude) = self.read_integer(tag)?;
+                visitor.visit_string(integer_to_string(negative, magnitude))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_number(visitor)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_number(visitor)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_number(visitor)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_number(visitor)
+    }
+
+    fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_number(visitor)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_number(visitor)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_number(visitor)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_number(visitor)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_number(visitor)
+    }
+
+    fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_number(visitor)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_number(visitor)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_number(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let tag = self.read_u8()?;
+        match tag {
+            // `{variant => content}`
+            MAP_EXT => match self.read_u32()? {
+                1 => visitor.visit_enum(Enum { de: self }),
+                n => Err(de::Error::invalid_length(n as usize, &"a single-entry map")),
+            },
+            ATOM_EXT | SMALL_ATOM_EXT | ATOM_UTF8_EXT | SMALL_ATOM_UTF8_EXT => {
+                let name = self.read_atom(tag)?;
+                visitor.visit_enum(IntoDeserializer::<Error>::into_deserializer(name))
+            }
+            BINARY_EXT => {
+                let name = std::str::from_utf8(self.read_binary()?).map_err(de::Error::custom)?;
+                visitor.visit_enum(IntoDeserializer::<Error>::into_deserializer(name))
+            }
+            tag => Err(Error::UnsupportedTag(tag)),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool bytes byte_buf unit unit_struct seq tuple tuple_struct map struct ignored_any
+    }
+}
+
+/// Elements of a list/tuple, or entries of a map.
+struct Access<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    remaining: usize,
+}
+
+impl Access<'_, '_> {
+    fn end(&self) -> Result<()> {
+        match self.remaining {
+            0 => Ok(()),
+            n => Err(de::Error::custom(format_args!("{n} unread elements"))),
+        }
+    }
+}
+
+impl<'de> SeqAccess<'de> for Access<'_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'de> MapAccess<'de> for Access<'_, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+struct Enum<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'de, 'a> EnumAccess<'de> for Enum<'a, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let variant = seed.deserialize(&mut *self.de)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Enum<'_, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        <()>::deserialize(self.de)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(self.de, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_map(self.de, visitor)
+    }
+}
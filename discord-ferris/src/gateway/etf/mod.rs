@@ -0,0 +1,86 @@
+//! Erlang External Term Format (`encoding=etf`) as a serde data format.
+//!
+//! Only the subset Discord speaks is supported: integers (including
+//! `SMALL_BIG_EXT` snowflakes), floats, atoms, binaries, lists, tuples and
+//! maps. Mapping to the serde data model:
+//!
+//! - atoms `nil`, `true` and `false` are unit/`None` and booleans; any other
+//!   atom is a string,
+//! - binaries are strings (or bytes when they are not UTF-8),
+//! - lists and tuples are sequences, maps are maps/structs,
+//! - big integers above 2^53 are snowflakes, so self-describing reads
+//!   (`deserialize_any`) yield their decimal string, as Discord sends them in
+//!   JSON; smaller ones (e.g. millisecond timestamps) and typed integer fields
+//!   get the number,
+//! - string fields accept any integer and get its decimal representation.
+//!
+//! The encoder writes strings as binaries, `None`/`()` as `nil` and sequences
+//! as lists, which is what the gateway expects for outgoing payloads.
+
+mod de;
+mod ser;
+
+pub use de::{Deserializer, from_slice};
+pub use ser::{Serializer, to_vec};
+
+use std::fmt::Display;
+
+use serde_json::value::RawValue;
+
+pub(crate) const VERSION: u8 = 131;
+
+pub(crate) const NEW_FLOAT_EXT: u8 = 70;
+pub(crate) const SMALL_INTEGER_EXT: u8 = 97;
+pub(crate) const INTEGER_EXT: u8 = 98;
+pub(crate) const FLOAT_EXT: u8 = 99;
+pub(crate) const ATOM_EXT: u8 = 100;
+pub(crate) const SMALL_TUPLE_EXT: u8 = 104;
+pub(crate) const LARGE_TUPLE_EXT: u8 = 105;
+pub(crate) const NIL_EXT: u8 = 106;
+pub(crate) const STRING_EXT: u8 = 107;
+pub(crate) const LIST_EXT: u8 = 108;
+pub(crate) const BINARY_EXT: u8 = 109;
+pub(crate) const SMALL_BIG_EXT: u8 = 110;
+pub(crate) const LARGE_BIG_EXT: u8 = 111;
+pub(crate) const MAP_EXT: u8 = 116;
+pub(crate) const SMALL_ATOM_EXT: u8 = 115;
+pub(crate) const ATOM_UTF8_EXT: u8 = 118;
+pub(crate) const SMALL_ATOM_UTF8_EXT: u8 = 119;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0}")]
+    Message(String),
+    #[error("unexpected end of input")]
+    Eof,
+    #[error("unsupported ETF version {0}")]
+    Version(u8),
+    #[error("unsupported ETF term tag {0}")]
+    UnsupportedTag(u8),
+    #[error("integer does not fit in 128 bits")]
+    IntegerOverflow,
+    #[error("improper list tail")]
+    ImproperList,
+    #[error("trailing bytes after term")]
+    TrailingBytes,
+}
+
+impl serde::de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+impl serde::ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+/// Transcodes an ETF term into JSON, following the mapping above.
+pub fn to_json(bytes: &[u8]) -> Result<Box<RawValue>> {
+    let value: serde_json::Value = from_slice(bytes)?;
+    serde_json::value::to_raw_value(&value).map_err(serde::de::Error::custom)
+}
@@ -1,34 +1,79 @@
+use std::sync::Arc;
+
+use crate::gateway::encoding::GatewayEncoding;
+use crate::gateway::shard::{CloseMode, ShardHandle};
 use crate::log;
 use tokio::select;
-use tokio::sync::{mpsc, watch};
-use tokio::time::{Duration, interval};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time::{Duration, Instant, interval};
 use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, frame::coding::CloseCode};
+
+/// Signals from the reader to the heartbeat task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeartbeatSignal {
+    /// op 1: the gateway asked for an immediate heartbeat.
+    Request,
+    /// op 11: the last heartbeat was acknowledged.
+    Ack,
+}
 
 /// Heartbeat loop (sends op=1 with latest seq).
+///
+/// A tick that finds the previous heartbeat still unacknowledged marks the
+/// connection as a zombie: the socket is closed with 4000 (keeping the session
+/// resumable) and `zombie_tx` fires so the reader stops.
+#[allow(clippy::too_many_arguments)]
 pub async fn run_heartbeat(
     writer_tx: mpsc::UnboundedSender<Message>,
     interval_ms: u64,
-    mut immediate_rx: mpsc::Receiver<()>,
+    mut signal_rx: mpsc::Receiver<HeartbeatSignal>,
     last_seq_rx: watch::Receiver<Option<i64>>,
-    mut shutdown_rx: watch::Receiver<bool>,
+    mut shutdown_rx: watch::Receiver<Option<CloseMode>>,
+    zombie_tx: oneshot::Sender<()>,
+    shard: Arc<ShardHandle>,
+    encoding: GatewayEncoding,
 ) {
     let mut ticker = interval(Duration::from_millis(interval_ms));
+    // When the last unacknowledged heartbeat went out.
+    let mut pending: Option<Instant> = None;
     loop {
         select! {
             _ = ticker.tick() => {
-                if let Err(e) = send_heartbeat(&writer_tx, last_seq_rx.borrow().clone()) {
-                    log!("HB", "send error: {e}");
+                if pending.is_some() {
+                    log!("WARN", "[shard {}] heartbeat not acknowledged — zombie connection", shard.id());
+                    shard.record_zombie();
+                    let _ = writer_tx.send(Message::Close(Some(CloseFrame {
+                        code: CloseCode::Library(4000),
+                        reason: "zombie".into(),
+                    })));
+                    let _ = zombie_tx.send(());
+                    break;
+                }
+                match send_heartbeat(&writer_tx, *last_seq_rx.borrow(), encoding) {
+                    Ok(()) => pending = Some(Instant::now()),
+                    Err(e) => log!("HB", "send error: {e}"),
                 }
             }
-            m = immediate_rx.recv() => {
-                if m.is_some() {
-                    if let Err(e) = send_heartbeat(&writer_tx, last_seq_rx.borrow().clone()) {
-                        log!("HB", "send error: {e}");
+            m = signal_rx.recv() => {
+                match m {
+                    Some(HeartbeatSignal::Request) => {
+                        match send_heartbeat(&writer_tx, *last_seq_rx.borrow(), encoding) {
+                            Ok(()) => pending = pending.or(Some(Instant::now())),
+                            Err(e) => log!("HB", "send error: {e}"),
+                        }
+                    }
+                    Some(HeartbeatSignal::Ack) => {
+                        if let Some(sent) = pending.take() {
+                            shard.record_latency(sent.elapsed());
+                        }
                     }
+                    // Reader is gone; the session is over.
+                    None => break,
                 }
             }
             _ = shutdown_rx.changed() => {
-                if *shutdown_rx.borrow() { break; }
+                if shutdown_rx.borrow().is_some() { break; }
             }
         }
     }
@@ -37,8 +82,9 @@ pub async fn run_heartbeat(
 pub fn send_heartbeat(
     writer_tx: &mpsc::UnboundedSender<Message>,
     seq: Option<i64>,
+    encoding: GatewayEncoding,
 ) -> anyhow::Result<()> {
     let heartbeat = serde_json::json!({ "op": 1, "d": seq });
-    writer_tx.send(Message::Text(heartbeat.to_string().into()))?;
+    writer_tx.send(encoding.encode(&heartbeat)?)?;
     Ok(())
 }
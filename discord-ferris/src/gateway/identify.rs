@@ -0,0 +1,144 @@
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant, sleep_until};
+
+use crate::gateway::shard::ShardId;
+use crate::log;
+use crate::models::gateway::{
+    GatewayIdentifyData, GatewayIdentifyProperties, GatewayIntents, GatewayPresenceUpdateData,
+};
+use crate::models::payloads::APIGatewaySessionStartLimit;
+
+/// Window in which each rate-limit bucket may IDENTIFY once.
+const IDENTIFY_WINDOW: Duration = Duration::from_secs(5);
+
+/// Length of the session start budget once `reset_after` elapses.
+const SESSION_START_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Session-independent part of IDENTIFY, shared by every shard.
+///
+/// `shard` and `presence` are filled in per connection.
+#[derive(Clone, Debug)]
+pub struct IdentifyConfig {
+    pub token: String,
+    pub intents: GatewayIntents,
+    pub properties: GatewayIdentifyProperties,
+    /// Per-payload zlib compression; needs the `zlib-stream` feature and
+    /// replaces transport compression.
+    pub compress: bool,
+    /// Member count (50–250) above which a guild's offline members are omitted.
+    pub large_threshold: Option<i64>,
+}
+
+impl IdentifyConfig {
+    pub fn new(token: impl Into<String>, intents: GatewayIntents) -> Self {
+        Self {
+            token: token.into(),
+            intents,
+            properties: default_properties(),
+            compress: false,
+            large_threshold: None,
+        }
+    }
+
+    /// IDENTIFY data for one connection.
+    pub fn data(
+        &self,
+        shard: (ShardId, ShardId),
+        presence: Option<GatewayPresenceUpdateData>,
+    ) -> GatewayIdentifyData {
+        GatewayIdentifyData {
+            token: self.token.clone(),
+            properties: self.properties.clone(),
+            compress: self.compress.then_some(true),
+            large_threshold: self.large_threshold,
+            shard: Some((shard.0 as i64, shard.1 as i64)),
+            presence,
+            intents: self.intents.bits() as i64,
+        }
+    }
+}
+
+/// `os` of the running platform; `browser` and `device` name the library.
+pub fn default_properties() -> GatewayIdentifyProperties {
+    GatewayIdentifyProperties {
+        os: std::env::consts::OS.to_owned(),
+        browser: "discord-ferris".to_owned(),
+        device: "discord-ferris".to_owned(),
+    }
+}
+
+/// Admits IDENTIFY payloads.
+///
+/// The default [`LocalIdentifyQueue`] only coordinates shards of this process;
+/// a multi-process deployment can plug in a shared coordinator instead.
+#[async_trait]
+pub trait IdentifyQueue: Send + Sync {
+    /// Resolves once `shard_id` may send IDENTIFY.
+    async fn acquire(&self, shard_id: ShardId);
+}
+
+/// In-process queue built from `session_start_limit`.
+///
+/// Shards share a bucket when `shard_id % max_concurrency` matches; each bucket
+/// admits one IDENTIFY per 5 seconds. When the daily budget runs out, callers
+/// wait for the reset instead of burning identifies.
+pub struct LocalIdentifyQueue {
+    buckets: Vec<Mutex<Option<Instant>>>,
+    budget: Mutex<SessionBudget>,
+}
+
+struct SessionBudget {
+    total: u32,
+    remaining: u32,
+    reset_at: Instant,
+}
+
+impl LocalIdentifyQueue {
+    pub fn new(limit: &APIGatewaySessionStartLimit) -> Self {
+        let max_concurrency = limit.max_concurrency.max(1) as usize;
+        Self {
+            buckets: (0..max_concurrency).map(|_| Mutex::new(None)).collect(),
+            budget: Mutex::new(SessionBudget {
+                total: limit.total.max(0) as u32,
+                remaining: limit.remaining.max(0) as u32,
+                reset_at: Instant::now() + Duration::from_millis(limit.reset_after.max(0) as u64),
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl IdentifyQueue for LocalIdentifyQueue {
+    async fn acquire(&self, shard_id: ShardId) {
+        // Holding the bucket lock across the sleep serializes shards of one bucket.
+        let bucket = shard_id as usize % self.buckets.len();
+        let mut last = self.buckets[bucket].lock().await;
+        if let Some(at) = *last {
+            sleep_until(at + IDENTIFY_WINDOW).await;
+        }
+
+        {
+            let mut budget = self.budget.lock().await;
+            if Instant::now() >= budget.reset_at {
+                budget.remaining = budget.total;
+                budget.reset_at = Instant::now() + SESSION_START_PERIOD;
+            }
+            if budget.remaining == 0 {
+                let wait = budget.reset_at - Instant::now();
+                log!(
+                    "WARN",
+                    "[shard {}] session start limit exhausted; waiting {:?} for reset",
+                    shard_id,
+                    wait
+                );
+                sleep_until(budget.reset_at).await;
+                budget.remaining = budget.total;
+                budget.reset_at = Instant::now() + SESSION_START_PERIOD;
+            }
+            budget.remaining = budget.remaining.saturating_sub(1);
+        }
+
+        *last = Some(Instant::now());
+    }
+}
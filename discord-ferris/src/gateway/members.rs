@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tokio::sync::oneshot;
+use tokio::time::Duration;
+
+use crate::gateway::shard::ShardId;
+use crate::models::gateway::{
+    GatewayGuildMembersChunkDispatchData, GatewayRequestGuildMembersData,
+    GatewayRequestGuildMembersDataBase, GatewayRequestGuildMembersDataWithQuery,
+    GatewayRequestGuildMembersDataWithUserIds, StringOrStrings,
+};
+use crate::models::payloads::{APIGuildMember, GatewayGuildMembersChunkPresence};
+
+/// How long a request waits for its last GUILD_MEMBERS_CHUNK.
+pub(crate) const MEMBER_CHUNK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Which members to request with op 8.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MemberFilter {
+    /// Usernames starting with `query`; an empty query with `limit: 0`
+    /// returns every member (requires `GUILD_MEMBERS`).
+    Query { query: String, limit: i64 },
+    /// Specific users, up to 100 ids.
+    UserIds(Vec<String>),
+}
+
+impl MemberFilter {
+    /// Every member of the guild.
+    pub fn all() -> Self {
+        MemberFilter::Query {
+            query: String::new(),
+            limit: 0,
+        }
+    }
+
+    pub(crate) fn into_request(
+        self,
+        guild_id: String,
+        presences: bool,
+        nonce: String,
+    ) -> GatewayRequestGuildMembersData {
+        let base = GatewayRequestGuildMembersDataBase {
+            guild_id,
+            presences: Some(presences),
+            nonce: Some(nonce),
+        };
+        match self {
+            MemberFilter::Query { query, limit } => {
+                GatewayRequestGuildMembersData::WithQuery(GatewayRequestGuildMembersDataWithQuery {
+                    base,
+                    query,
+                    limit,
+                })
+            }
+            MemberFilter::UserIds(ids) => GatewayRequestGuildMembersData::WithUserIds(
+                GatewayRequestGuildMembersDataWithUserIds {
+                    base,
+                    user_ids: StringOrStrings::Many(ids),
+                },
+            ),
+        }
+    }
+}
+
+/// Every chunk of one Request Guild Members response, merged.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GuildMembers {
+    pub guild_id: String,
+    pub members: Vec<APIGuildMember>,
+    /// Requested user ids that are not in the guild.
+    pub not_found: Vec<String>,
+    /// Only filled when presences were requested.
+    pub presences: Vec<GatewayGuildMembersChunkPresence>,
+}
+
+struct Pending {
+    members: GuildMembers,
+    received: i64,
+    done: oneshot::Sender<GuildMembers>,
+}
+
+/// Correlates GUILD_MEMBERS_CHUNK dispatches with pending requests by nonce.
+#[derive(Default)]
+pub(crate) struct MemberChunks {
+    next_nonce: AtomicU64,
+    pending: Mutex<HashMap<String, Pending>>,
+}
+
+impl fmt::Debug for MemberChunks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemberChunks")
+            .field("pending", &self.pending.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl MemberChunks {
+    /// Registers a request and returns its nonce (at most 32 bytes).
+    pub fn register(
+        &self,
+        shard_id: ShardId,
+        guild_id: &str,
+    ) -> (String, oneshot::Receiver<GuildMembers>) {
+        let n = self.next_nonce.fetch_add(1, Ordering::Relaxed);
+        let nonce = format!("{shard_id}-{n}");
+        let (done, rx) = oneshot::channel();
+        let members = GuildMembers {
+            guild_id: guild_id.to_owned(),
+            ..Default::default()
+        };
+        self.pending.lock().unwrap().insert(
+            nonce.clone(),
+            Pending {
+                members,
+                received: 0,
+                done,
+            },
+        );
+        (nonce, rx)
+    }
+
+    pub fn cancel(&self, nonce: &str) {
+        self.pending.lock().unwrap().remove(nonce);
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        !self.pending.lock().unwrap().is_empty()
+    }
+
+    /// Merges a chunk; resolves its request once `chunk_count` chunks arrived.
+    pub fn feed(&self, chunk: GatewayGuildMembersChunkDispatchData) {
+        let Some(nonce) = chunk.nonce else { return };
+        let mut pending = self.pending.lock().unwrap();
+        let Some(entry) = pending.get_mut(&nonce) else {
+            return;
+        };
+
+        let acc = &mut entry.members;
+        acc.members.extend(chunk.members);
+        acc.presences.extend(chunk.presences.unwrap_or_default());
+        acc.not_found.extend(
+            chunk
+                .not_found
+                .unwrap_or_default()
+                .into_iter()
+                .map(|id| match id {
+                    serde_json::Value::String(id) => id,
+                    other => other.to_string(),
+                }),
+        );
+        entry.received += 1;
+
+        if entry.received < chunk.chunk_count {
+            return;
+        }
+        if let Some(entry) = pending.remove(&nonce) {
+            let _ = entry.done.send(entry.members);
+        }
+    }
+}
@@ -1,30 +1,83 @@
+pub mod compression;
+pub mod encoding;
+pub mod etf;
 pub mod heartbeat;
+pub mod identify;
+pub mod members;
+pub mod pipeline;
+pub mod ratelimit;
+pub mod record;
+pub mod session;
+pub mod shard;
+pub mod store;
 pub mod ws;
 
 use serde::Deserialize;
-use serde_json::value::RawValue;
-use tokio::sync::{mpsc, watch};
-use tokio_tungstenite::tungstenite::Message;
-
-use crate::models::gateway::GatewayDispatchEvents;
-
-/// Active Gateway session wires.
-pub struct Gateway {
-    pub session_id: String,
-    pub resume_gateway_url: String,
-    pub writer_tx: mpsc::UnboundedSender<Message>,
-    /// Dispatches with raw `d` (no eager JSON parse).
-    pub events_rx: mpsc::UnboundedReceiver<crate::models::gateway::GatewayDispatch<Box<RawValue>>>,
-    pub last_seq_rx: watch::Receiver<Option<i64>>,
-    pub shutdown_tx: watch::Sender<bool>,
+
+use crate::models::gateway::{GatewayCloseCodes, GatewayDispatchEvents};
+
+/// Why a connection ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Disconnect {
+    /// op 7: reconnect and RESUME.
+    Reconnect,
+    /// op 9: RESUME if `resumable`, otherwise a fresh IDENTIFY.
+    InvalidSession { resumable: bool },
+    /// A heartbeat went unacknowledged; the socket was closed to RESUME.
+    Zombie,
+    /// The socket was closed (`code` from the close frame, if any).
+    Closed { code: Option<u16> },
 }
 
-impl Drop for Gateway {
-    fn drop(&mut self) {
-        let _ = self.shutdown_tx.send(true);
+impl Disconnect {
+    pub fn action(&self) -> CloseAction {
+        match self {
+            Disconnect::Reconnect | Disconnect::Zombie => CloseAction::Resume,
+            Disconnect::InvalidSession { resumable: true } => CloseAction::Resume,
+            Disconnect::InvalidSession { resumable: false } => CloseAction::Identify,
+            Disconnect::Closed { code } => close_action(*code),
+        }
     }
 }
 
+/// How to recover from a dropped session.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CloseAction {
+    /// Reconnect and RESUME.
+    Resume,
+    /// Reconnect with a fresh IDENTIFY.
+    Identify,
+    /// Do not reconnect; the configuration must be fixed.
+    Fatal(GatewayCloseCodes),
+}
+
+/// Documented policy for gateway close codes.
+/// Codes outside the 4000 range (or none at all) are treated as transient.
+pub fn close_action(code: Option<u16>) -> CloseAction {
+    use GatewayCloseCodes as C;
+    match code.and_then(GatewayCloseCodes::from_code) {
+        None => CloseAction::Resume,
+        Some(C::InvalidSeq | C::SessionTimedOut) => CloseAction::Identify,
+        Some(
+            code @ (C::AuthenticationFailed
+            | C::InvalidShard
+            | C::ShardingRequired
+            | C::InvalidAPIVersion
+            | C::InvalidIntents
+            | C::DisallowedIntents),
+        ) => CloseAction::Fatal(code),
+        Some(_) => CloseAction::Resume,
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GatewayError {
+    #[error("gateway closed with fatal code {0:?}")]
+    Fatal(GatewayCloseCodes),
+    #[error(transparent)]
+    Transport(#[from] anyhow::Error),
+}
+
 pub trait EventPayload: for<'de> Deserialize<'de> + Send + 'static {
     const EVENT: GatewayDispatchEvents;
 }
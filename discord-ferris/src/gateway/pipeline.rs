@@ -0,0 +1,217 @@
+//! Bounded queue between the shard readers and the event loop.
+//!
+//! Readers push dispatches straight into the queue, so a `Block` policy holds
+//! up the socket itself rather than an intermediate buffer.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{Notify, Semaphore, TryAcquireError};
+
+use crate::gateway::shard::ShardDispatch;
+use crate::models::gateway::GatewayDispatchEvents;
+
+/// Dispatches buffered between the shards and the event loop.
+pub const DEFAULT_EVENT_CAPACITY: usize = 1024;
+
+/// What a shard does with a dispatch that arrives while the queue is full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait for room; the shard stops reading meanwhile. Stalls longer than
+    /// the heartbeat interval end in a zombie reconnect.
+    #[default]
+    Block,
+    /// Replace the oldest queued dispatch of the same kind; drop the new one
+    /// if none is queued.
+    DropOldest,
+    /// Drop the new dispatch.
+    DropNewest,
+}
+
+/// Overflow policy per dispatch kind.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EventPolicies {
+    /// Applies to kinds without an entry in `by_kind`.
+    pub default: OverflowPolicy,
+    pub by_kind: HashMap<GatewayDispatchEvents, OverflowPolicy>,
+}
+
+impl EventPolicies {
+    pub fn set(&mut self, kind: GatewayDispatchEvents, policy: OverflowPolicy) -> &mut Self {
+        self.by_kind.insert(kind, policy);
+        self
+    }
+
+    pub fn get(&self, kind: &GatewayDispatchEvents) -> OverflowPolicy {
+        self.by_kind.get(kind).copied().unwrap_or(self.default)
+    }
+}
+
+/// Queue depth and dropped dispatches, shared with [`Ctx`](crate::framework::context::Ctx).
+#[derive(Debug, Default)]
+pub struct EventStats {
+    queued: AtomicUsize,
+    dropped: Mutex<HashMap<GatewayDispatchEvents, u64>>,
+}
+
+impl EventStats {
+    /// Dispatches waiting for the event loop.
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    /// Dispatches dropped by a full queue, over all kinds.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.lock().unwrap().values().sum()
+    }
+
+    pub fn dropped_of(&self, kind: &GatewayDispatchEvents) -> u64 {
+        self.dropped.lock().unwrap().get(kind).copied().unwrap_or(0)
+    }
+
+    pub fn dropped_by_kind(&self) -> HashMap<GatewayDispatchEvents, u64> {
+        self.dropped.lock().unwrap().clone()
+    }
+
+    fn record_drop(&self, kind: &GatewayDispatchEvents) {
+        *self
+            .dropped
+            .lock()
+            .unwrap()
+            .entry(kind.clone())
+            .or_default() += 1;
+    }
+}
+
+struct Shared {
+    queue: Mutex<VecDeque<ShardDispatch>>,
+    /// One permit per free slot; closed once the receiver is gone.
+    slots: Semaphore,
+    readable: Notify,
+    senders: AtomicUsize,
+    policies: EventPolicies,
+    stats: Arc<EventStats>,
+}
+
+/// Creates a queue holding at most `capacity` dispatches.
+pub(crate) fn channel(
+    capacity: usize,
+    policies: EventPolicies,
+    stats: Arc<EventStats>,
+) -> (EventSender, EventReceiver) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(VecDeque::with_capacity(capacity)),
+        slots: Semaphore::new(capacity.max(1)),
+        readable: Notify::new(),
+        senders: AtomicUsize::new(1),
+        policies,
+        stats,
+    });
+    (
+        EventSender {
+            shared: Arc::clone(&shared),
+        },
+        EventReceiver { shared },
+    )
+}
+
+/// The event loop stopped receiving.
+#[derive(Debug)]
+pub struct Closed;
+
+/// Shard side of the queue.
+pub struct EventSender {
+    shared: Arc<Shared>,
+}
+
+impl EventSender {
+    /// Queues `ev`, applying its kind's [`OverflowPolicy`] when full.
+    pub async fn send(&self, ev: ShardDispatch) -> Result<(), Closed> {
+        let shared = &self.shared;
+        let policy = shared.policies.get(&ev.1.t);
+        let permit = match policy {
+            OverflowPolicy::Block => shared.slots.acquire().await.map_err(|_| Closed)?,
+            _ => match shared.slots.try_acquire() {
+                Ok(permit) => permit,
+                Err(TryAcquireError::Closed) => return Err(Closed),
+                Err(TryAcquireError::NoPermits) => {
+                    let ev = match policy {
+                        OverflowPolicy::DropOldest => match self.replace_oldest(ev) {
+                            Some(ev) => ev,
+                            None => return Ok(()),
+                        },
+                        _ => ev,
+                    };
+                    shared.stats.record_drop(&ev.1.t);
+                    return Ok(());
+                }
+            },
+        };
+        // The receiver hands the slot back once it pops the dispatch.
+        permit.forget();
+        shared.queue.lock().unwrap().push_back(ev);
+        shared.stats.queued.fetch_add(1, Ordering::Relaxed);
+        shared.readable.notify_one();
+        Ok(())
+    }
+
+    /// Swaps `ev` for the oldest queued dispatch of its kind; hands `ev` back
+    /// if there is none.
+    fn replace_oldest(&self, ev: ShardDispatch) -> Option<ShardDispatch> {
+        let mut queue = self.shared.queue.lock().unwrap();
+        let Some(pos) = queue.iter().position(|(_, old)| old.t == ev.1.t) else {
+            return Some(ev);
+        };
+        queue.remove(pos);
+        self.shared.stats.record_drop(&ev.1.t);
+        queue.push_back(ev);
+        None
+    }
+}
+
+impl Clone for EventSender {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::AcqRel);
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl Drop for EventSender {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.readable.notify_one();
+        }
+    }
+}
+
+/// Event loop side of the queue.
+pub struct EventReceiver {
+    shared: Arc<Shared>,
+}
+
+impl EventReceiver {
+    /// Next dispatch; `None` once every shard has stopped and the queue is drained.
+    pub async fn recv(&mut self) -> Option<ShardDispatch> {
+        loop {
+            let next = self.shared.queue.lock().unwrap().pop_front();
+            if let Some(ev) = next {
+                self.shared.slots.add_permits(1);
+                self.shared.stats.queued.fetch_sub(1, Ordering::Relaxed);
+                return Some(ev);
+            }
+            if self.shared.senders.load(Ordering::Acquire) == 0 {
+                return None;
+            }
+            self.shared.readable.notified().await;
+        }
+    }
+}
+
+impl Drop for EventReceiver {
+    fn drop(&mut self) {
+        self.shared.slots.close();
+    }
+}
@@ -0,0 +1,52 @@
+use tokio::time::{Duration, Instant};
+
+/// Gateway commands Discord accepts per connection and window; going over
+/// closes the socket with `RateLimited` (4008).
+pub const COMMANDS_PER_WINDOW: u32 = 120;
+
+pub const COMMAND_WINDOW: Duration = Duration::from_secs(60);
+
+/// Slots user commands may not use, kept for heartbeats and IDENTIFY/RESUME.
+pub const RESERVED_COMMANDS: u32 = 5;
+
+/// User commands queued behind an exhausted bucket before sends are rejected.
+pub const COMMAND_QUEUE_CAPACITY: usize = 128;
+
+/// Fixed-window counter for one connection.
+///
+/// Library frames (heartbeats, IDENTIFY, RESUME) always go out and are
+/// counted; user commands only get the slots above [`RESERVED_COMMANDS`].
+#[derive(Debug)]
+pub(crate) struct CommandBucket {
+    window_start: Instant,
+    used: u32,
+}
+
+impl CommandBucket {
+    pub fn new() -> Self {
+        Self {
+            window_start: Instant::now(),
+            used: 0,
+        }
+    }
+
+    /// Starts a new window once the current one has elapsed.
+    pub fn refresh(&mut self) {
+        if Instant::now() >= self.reset_at() {
+            self.window_start = Instant::now();
+            self.used = 0;
+        }
+    }
+
+    pub fn reset_at(&self) -> Instant {
+        self.window_start + COMMAND_WINDOW
+    }
+
+    pub fn has_user_slot(&self) -> bool {
+        self.used < COMMANDS_PER_WINDOW - RESERVED_COMMANDS
+    }
+
+    pub fn take(&mut self) {
+        self.used += 1;
+    }
+}
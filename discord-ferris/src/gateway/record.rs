@@ -0,0 +1,214 @@
+//! Gateway traffic recorded to JSONL and replayed without a network.
+//!
+//! A [`Recorder`] appends every frame the shards receive, one JSON object per
+//! line; a [`Replay`] feeds the dispatches of such a file back into a
+//! [`Router`], so a payload that broke a handler can be reproduced exactly.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+use crate::framework::context::Ctx;
+use crate::framework::router::Router;
+use crate::gateway::encoding::Frame;
+use crate::gateway::shard::ShardId;
+use crate::log;
+use crate::models::gateway::{GatewayDispatch, GatewayDispatchEvents, GatewayOpcodes};
+
+/// One line of a recording.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// Milliseconds since the Unix epoch at which the frame was received.
+    pub ts: u64,
+    pub shard: ShardId,
+    pub op: i64,
+    #[serde(default)]
+    pub t: Option<String>,
+    #[serde(default)]
+    pub s: Option<i64>,
+    /// `d` exactly as received (converted to JSON for ETF).
+    #[serde(default)]
+    pub d: Option<Box<RawValue>>,
+}
+
+/// Appends received frames to a JSONL file; shared by every shard.
+///
+/// Frames are written by a thread of its own, so shards never wait on the
+/// disk. Dropping the recorder writes whatever is still queued.
+pub struct Recorder {
+    tx: Option<mpsc::Sender<RecordedFrame>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl Recorder {
+    /// Appends to `path`, creating it if needed.
+    pub fn create(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let (tx, rx) = mpsc::channel();
+        let writer = std::thread::Builder::new()
+            .name("discord-ferris-recorder".to_owned())
+            .spawn(move || write_frames(LineWriter::new(file), rx))?;
+        Ok(Self {
+            tx: Some(tx),
+            writer: Some(writer),
+        })
+    }
+
+    /// Queues `frame` for the writer thread.
+    pub(crate) fn record(&self, shard: ShardId, frame: &Frame) {
+        let line = RecordedFrame {
+            ts: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            shard,
+            op: frame.op,
+            t: frame.t.clone(),
+            s: frame.s,
+            d: frame.d.clone(),
+        };
+        if let Some(tx) = &self.tx
+            && tx.send(line).is_err()
+        {
+            log!(
+                "WARN",
+                "[shard {shard}] recorder stopped; frame not recorded"
+            );
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        // Closing the channel lets the writer finish the queue and exit.
+        drop(self.tx.take());
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Writes queued frames until every sender is gone.
+fn write_frames(mut out: LineWriter<File>, rx: mpsc::Receiver<RecordedFrame>) {
+    for line in rx {
+        let result = serde_json::to_writer(&mut out, &line)
+            .map_err(anyhow::Error::from)
+            .and_then(|()| Ok(out.write_all(b"\n")?));
+        if let Err(e) = result {
+            log!("WARN", "[shard {}] could not record frame: {e}", line.shard);
+        }
+    }
+}
+
+/// Dispatches of a recording, fed into a [`Router`] in order.
+pub struct Replay {
+    frames: Vec<RecordedFrame>,
+    speed: Option<f64>,
+}
+
+impl Replay {
+    /// Reads every frame of a recording.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut frames = vec![];
+        for (n, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let frame =
+                serde_json::from_str(&line).map_err(|e| anyhow::anyhow!("line {}: {e}", n + 1))?;
+            frames.push(frame);
+        }
+        Ok(Self::from_frames(frames))
+    }
+
+    pub fn from_frames(frames: Vec<RecordedFrame>) -> Self {
+        Self {
+            frames,
+            speed: None,
+        }
+    }
+
+    /// Keeps the recorded gaps between frames, divided by `speed`
+    /// (1.0 is real time). Without it, frames are fed back to back.
+    pub fn speed(&mut self, speed: f64) -> &mut Self {
+        self.speed = (speed > 0.0).then_some(speed);
+        self
+    }
+
+    pub fn frames(&self) -> &[RecordedFrame] {
+        &self.frames
+    }
+
+    /// Dispatches every recorded op 0 frame through `router`, on the shard it
+    /// was received by. Returns how many were dispatched.
+    pub async fn run(&self, router: &Router, ctx: &Ctx) -> usize {
+        let mut dispatched = 0;
+        let mut last_ts = None;
+        for frame in &self.frames {
+            if let (Some(speed), Some(last)) = (self.speed, last_ts) {
+                let gap = frame.ts.saturating_sub(last) as f64 / speed;
+                tokio::time::sleep(Duration::from_secs_f64(gap / 1000.0)).await;
+            }
+            last_ts = Some(frame.ts);
+
+            if frame.op != 0 {
+                continue;
+            }
+            let Some(t) = &frame.t else { continue };
+            let ev = GatewayDispatch {
+                op: GatewayOpcodes::Dispatch,
+                t: GatewayDispatchEvents::from_name(t),
+                s: frame.s.unwrap_or(0),
+                d: frame.d.clone().unwrap_or_else(|| RawValue::NULL.to_owned()),
+            };
+            router
+                .dispatch(&ctx.for_shard(frame.shard), Arc::new(ev))
+                .await;
+            dispatched += 1;
+        }
+        dispatched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queued_frames_are_written_on_drop() {
+        let path = std::env::temp_dir().join(format!(
+            "discord-ferris-recorder-{}.jsonl",
+            std::process::id()
+        ));
+        let recorder = Recorder::create(&path).unwrap();
+        for s in 1..=3 {
+            let frame = Frame {
+                op: 0,
+                s: Some(s),
+                t: Some("MESSAGE_CREATE".to_owned()),
+                d: Some(RawValue::from_string(format!(r#"{{"n":{s}}}"#)).unwrap()),
+            };
+            recorder.record(2, &frame);
+        }
+        drop(recorder);
+
+        let replay = Replay::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let frames = replay.frames();
+        assert_eq!(frames.len(), 3);
+        for (frame, s) in frames.iter().zip(1..) {
+            assert_eq!(frame.shard, 2);
+            assert_eq!(frame.s, Some(s));
+            assert_eq!(frame.t.as_deref(), Some("MESSAGE_CREATE"));
+            assert_eq!(frame.d.as_ref().unwrap().get(), format!(r#"{{"n":{s}}}"#));
+        }
+    }
+}
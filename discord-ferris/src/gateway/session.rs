@@ -0,0 +1,516 @@
+//! Gateway session state machine of one shard.
+//!
+//! A [`Shard`] owns its socket and loops through
+//! `Connecting → Hello → Identifying/Resuming → Ready`, falling back to
+//! `Reconnecting` whenever the connection drops. IDENTIFY and RESUME share
+//! every step except the payload sent after HELLO.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use tokio::sync::{mpsc, oneshot, watch};
+
+use crate::gateway::compression::GatewayCompression;
+use crate::gateway::encoding::{Frame, GatewayEncoding};
+use crate::gateway::heartbeat::{HeartbeatSignal, run_heartbeat};
+use crate::gateway::identify::{IdentifyConfig, IdentifyQueue};
+use crate::gateway::pipeline::EventSender;
+use crate::gateway::record::Recorder;
+use crate::gateway::shard::{CloseMode, ReconnectPolicy, ShardHandle, ShardId};
+use crate::gateway::store::{SessionInfo, SessionStore};
+use crate::gateway::ws::{self, Connection, DISCORD_GATEWAY_URL, Received};
+use crate::gateway::{CloseAction, Disconnect, GatewayError};
+use crate::http::proxy::Proxy;
+use crate::log;
+use crate::models::gateway::{GatewayDispatch, GatewayDispatchEvents as GwEvt, GatewayOpcodes};
+
+/// Where a shard is in its connection lifecycle.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ShardState {
+    /// Opening the websocket.
+    #[default]
+    Connecting,
+    /// Waiting for HELLO.
+    Hello,
+    /// IDENTIFY sent; waiting for READY.
+    Identifying,
+    /// RESUME sent; replaying missed dispatches until RESUMED.
+    Resuming,
+    /// Session established; dispatches are flowing.
+    Ready,
+    /// The connection dropped; waiting before the next attempt.
+    Reconnecting,
+    /// Shut down, or closed with a fatal code.
+    Stopped,
+}
+
+#[inline]
+fn jitter(min: u64, max: u64) -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .subsec_nanos() as u64;
+    let mut state = nanos ^ 0x5DEECE66D;
+    state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+    min + (state % (max - min + 1))
+}
+
+#[derive(Deserialize)]
+struct HelloD {
+    heartbeat_interval: u64,
+}
+
+/// Settings shared by every shard of a manager.
+#[derive(Clone)]
+pub(crate) struct ShardConfig {
+    pub gateway_url: String,
+    pub identify: Arc<IdentifyConfig>,
+    pub identify_queue: Arc<dyn IdentifyQueue>,
+    pub encoding: GatewayEncoding,
+    /// `compress=zlib-stream`, unless IDENTIFY asks for payload compression.
+    pub transport_compression: bool,
+    pub reconnect: ReconnectPolicy,
+    pub session_store: Option<Arc<dyn SessionStore>>,
+    pub recorder: Option<Arc<Recorder>>,
+    pub proxy: Option<Proxy>,
+}
+
+/// How one connection ended.
+enum End {
+    Shutdown,
+    Dropped(Disconnect),
+    Failed(anyhow::Error),
+}
+
+/// Keeps one shard connected until shutdown or a fatal close code.
+pub(crate) struct Shard {
+    handle: Arc<ShardHandle>,
+    total: ShardId,
+    config: ShardConfig,
+    events: EventSender,
+    shutdown_rx: watch::Receiver<Option<CloseMode>>,
+    /// Session to RESUME after a drop.
+    session: Option<SessionInfo>,
+}
+
+impl Shard {
+    pub fn new(
+        handle: Arc<ShardHandle>,
+        total: ShardId,
+        config: ShardConfig,
+        events: EventSender,
+        shutdown_rx: watch::Receiver<Option<CloseMode>>,
+    ) -> Self {
+        Self {
+            handle,
+            total,
+            config,
+            events,
+            shutdown_rx,
+            session: None,
+        }
+    }
+
+    #[inline]
+    fn id(&self) -> ShardId {
+        self.handle.id()
+    }
+
+    /// Hands `frame` to the recorder, if any.
+    fn record(&self, frame: Frame) -> Frame {
+        if let Some(recorder) = &self.config.recorder {
+            recorder.record(self.id(), &frame);
+        }
+        frame
+    }
+
+    fn set_state(&self, state: ShardState) {
+        self.handle.set_state(state);
+    }
+
+    pub async fn run(mut self) -> Result<(), GatewayError> {
+        self.restore_session().await;
+        let result = self.run_until_stopped().await;
+        self.persist_session(result.is_ok()).await;
+        self.set_state(ShardState::Stopped);
+        result
+    }
+
+    /// Picks up the session saved by a previous process, so the first
+    /// connection tries RESUME before IDENTIFY.
+    async fn restore_session(&mut self) {
+        let Some(store) = &self.config.session_store else {
+            return;
+        };
+        match store.load(self.id()).await {
+            Ok(Some(session)) => {
+                log!(
+                    "GW",
+                    "[shard {}] restored session_id={} (seq={:?})",
+                    self.id(),
+                    session.session_id,
+                    session.seq
+                );
+                self.session = Some(session);
+            }
+            Ok(None) => {}
+            Err(e) => log!(
+                "WARN",
+                "[shard {}] could not load saved session: {e}",
+                self.id()
+            ),
+        }
+    }
+
+    /// Saves the session after a resumable shutdown; forgets it otherwise.
+    async fn persist_session(&self, graceful: bool) {
+        let Some(store) = &self.config.session_store else {
+            return;
+        };
+        let resumable = graceful && self.close_mode() == CloseMode::Resumable;
+        let result = match (&self.session, resumable) {
+            (Some(session), true) => store.save(self.id(), session).await,
+            _ => store.remove(self.id()).await,
+        };
+        if let Err(e) = result {
+            log!(
+                "WARN",
+                "[shard {}] could not persist session: {e}",
+                self.id()
+            );
+        }
+    }
+
+    async fn run_until_stopped(&mut self) -> Result<(), GatewayError> {
+        let mut shutdown_rx = self.shutdown_rx.clone();
+        // Consecutive connections that never reached Ready.
+        let mut failures = 0;
+        loop {
+            if self.session.is_none() {
+                tokio::select! {
+                    _ = self.config.identify_queue.acquire(self.id()) => {}
+                    _ = shutdown_rx.wait_for(Option::is_some) => return Ok(()),
+                }
+            }
+
+            // Polled first so a running connection can still send its close frame.
+            let end = tokio::select! {
+                biased;
+                end = self.connect() => end,
+                _ = shutdown_rx.wait_for(Option::is_some) => End::Shutdown,
+            };
+            let established = self.handle.state() == ShardState::Ready;
+            let (reason, error) = match end {
+                End::Shutdown => return Ok(()),
+                End::Dropped(reason) => (reason, None),
+                End::Failed(e) => (Disconnect::Closed { code: None }, Some(e)),
+            };
+            self.set_state(ShardState::Reconnecting);
+
+            let mut delay = match reason.action() {
+                CloseAction::Fatal(code) => return Err(GatewayError::Fatal(code)),
+                CloseAction::Identify => {
+                    log!(
+                        "WARN",
+                        "[shard {}] Session not resumable — fresh IDENTIFY…",
+                        self.id()
+                    );
+                    self.session = None;
+                    Duration::from_millis(jitter(1000, 5000))
+                }
+                CloseAction::Resume => Duration::ZERO,
+            };
+            if established {
+                failures = 0;
+            } else {
+                failures += 1;
+                let Some(backoff) = self.config.reconnect.backoff(failures) else {
+                    let err =
+                        error.unwrap_or_else(|| anyhow::anyhow!("session ended ({reason:?})"));
+                    return Err(GatewayError::Transport(
+                        err.context(format!("giving up after {failures} attempt(s)")),
+                    ));
+                };
+                delay = delay.max(backoff);
+            }
+
+            match &error {
+                Some(e) => log!(
+                    "ERR",
+                    "[shard {}] connection failed: {e}. Retrying in {:?}…",
+                    self.id(),
+                    delay
+                ),
+                None => log!(
+                    "WARN",
+                    "[shard {}] session ended ({reason:?}) — reconnecting in {:?}…",
+                    self.id(),
+                    delay
+                ),
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = shutdown_rx.wait_for(Option::is_some) => return Ok(()),
+            }
+        }
+    }
+
+    /// Runs one connection from the socket opening until it drops.
+    async fn connect(&mut self) -> End {
+        let id = self.id();
+        let encoding = self.config.encoding;
+        let compression = GatewayCompression::new(
+            self.config.transport_compression,
+            self.config.identify.compress,
+        );
+
+        self.set_state(ShardState::Connecting);
+        let url = match &self.session {
+            Some(session) => session.resume_gateway_url.clone(),
+            None => self.config.gateway_url.clone(),
+        };
+        log!("GW", "[shard {}/{}] connecting to {url}", id, self.total);
+        let mut conn =
+            match ws::open(&url, encoding, compression, self.config.proxy.as_ref(), id).await {
+                Ok(conn) => conn,
+                Err(e) => return End::Failed(e),
+            };
+
+        // HELLO
+        self.set_state(ShardState::Hello);
+        let heartbeat_interval_ms = loop {
+            let frame = match conn.recv().await {
+                Ok(Received::Payload(frame)) => self.record(frame),
+                Ok(Received::Closed { code }) => return End::Dropped(Disconnect::Closed { code }),
+                Err(e) => return End::Failed(e),
+            };
+            if frame.op != 10 {
+                continue;
+            }
+            let hello = frame
+                .d
+                .ok_or_else(|| anyhow::anyhow!("HELLO without d"))
+                .and_then(|d| Ok(serde_json::from_str::<HelloD>(d.get())?));
+            match hello {
+                Ok(hello) => {
+                    log!(
+                        "OK",
+                        "[shard {id}] received HELLO, heartbeat_interval={}ms",
+                        hello.heartbeat_interval
+                    );
+                    break hello.heartbeat_interval;
+                }
+                Err(e) => return End::Failed(e),
+            }
+        };
+
+        // Heartbeat; stops once `heartbeat_tx` is dropped with this connection.
+        let (heartbeat_tx, heartbeat_rx) = mpsc::channel::<HeartbeatSignal>(8);
+        let (zombie_tx, mut zombie_rx) = oneshot::channel::<()>();
+        let seq = self.session.as_ref().and_then(|s| s.seq);
+        let (last_seq_tx, last_seq_rx) = watch::channel::<Option<i64>>(seq);
+        tokio::spawn(run_heartbeat(
+            conn.writer_tx.clone(),
+            heartbeat_interval_ms,
+            heartbeat_rx,
+            last_seq_rx,
+            self.shutdown_rx.clone(),
+            zombie_tx,
+            Arc::clone(&self.handle),
+            encoding,
+        ));
+
+        // IDENTIFY or RESUME
+        if let Err(e) = self.handshake(&conn) {
+            return End::Failed(e);
+        }
+
+        let mut shutdown_rx = self.shutdown_rx.clone();
+        loop {
+            let received = tokio::select! {
+                received = conn.recv() => received,
+                _ = &mut zombie_rx => return End::Dropped(Disconnect::Zombie),
+                _ = shutdown_rx.wait_for(Option::is_some) => {
+                    self.close_for_shutdown(&conn);
+                    return End::Shutdown;
+                }
+            };
+            let frame = match received {
+                Ok(Received::Payload(frame)) => self.record(frame),
+                Ok(Received::Closed { code }) => {
+                    log!("WARN", "[shard {id}] closed by gateway (code={code:?})");
+                    return End::Dropped(Disconnect::Closed { code });
+                }
+                Err(e) => return End::Failed(e),
+            };
+
+            if let Some(s) = frame.s {
+                let _ = last_seq_tx.send_replace(Some(s));
+                if let Some(session) = &mut self.session {
+                    session.seq = Some(s);
+                }
+            }
+
+            if frame.op == 0 {
+                if self.dispatch(&conn, frame).await.is_err() {
+                    // The event loop is gone; nothing left to deliver to.
+                    self.close_for_shutdown(&conn);
+                    return End::Shutdown;
+                }
+                continue;
+            }
+
+            match frame.op {
+                1 => {
+                    let _ = heartbeat_tx.try_send(HeartbeatSignal::Request);
+                }
+                7 => {
+                    log!("WARN", "[shard {id}] RECONNECT requested");
+                    conn.close_resumable("reconnect");
+                    return End::Dropped(Disconnect::Reconnect);
+                }
+                9 => {
+                    let resumable = frame
+                        .d
+                        .and_then(|v| v.get().parse::<bool>().ok())
+                        .unwrap_or(false);
+                    log!(
+                        "WARN",
+                        "[shard {id}] INVALID_SESSION (resumable={resumable})"
+                    );
+                    conn.close_resumable("invalid session");
+                    return End::Dropped(Disconnect::InvalidSession { resumable });
+                }
+                11 => {
+                    let _ = heartbeat_tx.try_send(HeartbeatSignal::Ack);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// How the manager asked to close; keeps the session when a store can
+    /// save it if the event loop went away without asking.
+    fn close_mode(&self) -> CloseMode {
+        let requested = *self.shutdown_rx.borrow();
+        requested.unwrap_or(match self.config.session_store {
+            Some(_) => CloseMode::Resumable,
+            None => CloseMode::Normal,
+        })
+    }
+
+    fn close_for_shutdown(&self, conn: &Connection) {
+        match self.close_mode() {
+            CloseMode::Normal => conn.close_normal(),
+            CloseMode::Resumable => conn.close_resumable("shutdown"),
+        }
+    }
+
+    /// Sends RESUME when a session is kept, IDENTIFY otherwise.
+    fn handshake(&self, conn: &Connection) -> anyhow::Result<()> {
+        let id = self.id();
+        match &self.session {
+            Some(session) => {
+                let resume = serde_json::json!({
+                    "op": 6,
+                    "d": {
+                        "token": self.config.identify.token,
+                        "session_id": session.session_id,
+                        "seq": session.seq,
+                    }
+                });
+                conn.send(&resume)?;
+                self.set_state(ShardState::Resuming);
+                log!(
+                    "GW",
+                    "[shard {id}] payload op6 (resume) queued for session_id={} with seq={:?}",
+                    session.session_id,
+                    session.seq
+                );
+            }
+            None => {
+                // Last presence set on this shard survives re-IDENTIFY.
+                let data = self
+                    .config
+                    .identify
+                    .data((id, self.total), self.handle.presence());
+                let mut d = serde_json::to_value(data)?;
+                // Leave unset optional fields out rather than sending them as null.
+                if let Some(d) = d.as_object_mut() {
+                    d.retain(|_, v| !v.is_null());
+                }
+                conn.send(&serde_json::json!({ "op": 2, "d": d }))?;
+                self.set_state(ShardState::Identifying);
+                log!("GW", "[shard {id}] payload op2 (identify) queued");
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles READY/RESUMED and forwards every dispatch.
+    async fn dispatch(
+        &mut self,
+        conn: &Connection,
+        frame: Frame,
+    ) -> Result<(), crate::gateway::pipeline::Closed> {
+        let Some(t) = frame.t else { return Ok(()) };
+        let d = frame.d.unwrap_or_else(|| RawValue::NULL.to_owned());
+
+        match t.as_str() {
+            "READY" => {
+                #[derive(Deserialize)]
+                struct ReadyD<'a> {
+                    session_id: &'a str,
+                    #[serde(default)]
+                    resume_gateway_url: Option<&'a str>,
+                }
+                if let Ok(r) = serde_json::from_str::<ReadyD>(d.get()) {
+                    self.session = Some(SessionInfo {
+                        session_id: r.session_id.to_owned(),
+                        resume_gateway_url: r
+                            .resume_gateway_url
+                            .unwrap_or(DISCORD_GATEWAY_URL)
+                            .to_owned(),
+                        seq: frame.s,
+                    });
+                    log!(
+                        "OK",
+                        "[shard {}] authenticated (session_id={})",
+                        self.id(),
+                        r.session_id
+                    );
+                }
+                self.ready(conn);
+            }
+            "RESUMED" => {
+                log!("OK", "[shard {}] RESUMED successfully", self.id());
+                self.ready(conn);
+                // Presence updates may have been dropped while reconnecting.
+                if let Some(presence) = self.handle.presence() {
+                    let _ = self.handle.update_presence(presence);
+                }
+            }
+            _ => {}
+        }
+
+        // Unrecognised names are forwarded as `Unknown` rather than dropped.
+        let ev = GatewayDispatch {
+            op: GatewayOpcodes::Dispatch,
+            t: GwEvt::from_name(&t),
+            s: frame.s.unwrap_or(0),
+            d,
+        };
+        // Pending member requests first, then the event queue.
+        self.handle.observe(&ev);
+        self.events.send((self.id(), ev)).await
+    }
+
+    fn ready(&self, conn: &Connection) {
+        self.handle
+            .attach(conn.commands_tx.clone(), self.config.encoding);
+        self.set_state(ShardState::Ready);
+    }
+}
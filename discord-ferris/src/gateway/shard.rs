@@ -0,0 +1,649 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::value::RawValue;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::gateway::GatewayError;
+use crate::gateway::encoding::GatewayEncoding;
+use crate::gateway::identify::{IdentifyConfig, IdentifyQueue, LocalIdentifyQueue};
+use crate::gateway::members::{GuildMembers, MEMBER_CHUNK_TIMEOUT, MemberChunks, MemberFilter};
+use crate::gateway::pipeline::{
+    self, DEFAULT_EVENT_CAPACITY, EventPolicies, EventReceiver, EventStats,
+};
+use crate::gateway::record::Recorder;
+use crate::gateway::session::{Shard, ShardConfig, ShardState};
+use crate::gateway::store::SessionStore;
+use crate::http::Http;
+use crate::http::proxy::Proxy;
+use crate::log;
+use crate::models::gateway::{
+    GatewayActivityUpdateData, GatewayDispatch, GatewayDispatchEvents, GatewayPresenceUpdateData,
+};
+use crate::models::payloads::PresenceUpdateStatus;
+
+/// Zero-based shard index (`shard_id` in `[shard_id, num_shards]`).
+pub type ShardId = u32;
+
+/// A dispatch tagged with the shard that received it.
+pub type ShardDispatch = (ShardId, GatewayDispatch<Box<RawValue>>);
+
+/// Which shards this process runs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ShardRange {
+    /// Run every shard, using the count recommended by GET /gateway/bot.
+    #[default]
+    Recommended,
+    /// Run `ids` out of `total` shards (e.g. `0..4` of `8` on one of two processes).
+    Range { ids: Range<ShardId>, total: ShardId },
+}
+
+/// Close code shards send on shutdown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CloseMode {
+    /// 1000: ends the session.
+    #[default]
+    Normal,
+    /// 4000: the session stays resumable, and is saved if a
+    /// [`SessionStore`] is set.
+    Resumable,
+}
+
+/// Backoff between failed connection attempts of one shard.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    /// Delay after the first failure; doubled after each further one.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Consecutive failures before the shard gives up; `None` retries forever.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Delay before retrying after `failures` consecutive failures, or `None`
+    /// once the policy gives up.
+    pub(crate) fn backoff(&self, failures: u32) -> Option<Duration> {
+        if self.max_attempts.is_some_and(|max| failures >= max) {
+            return None;
+        }
+        let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+        Some(std::cmp::min(
+            self.initial_backoff.saturating_mul(factor),
+            self.max_backoff,
+        ))
+    }
+}
+
+/// Builds an op 3 payload for a bot (at most one activity, never AFK).
+pub(crate) fn presence_update(
+    status: PresenceUpdateStatus,
+    activity: Option<GatewayActivityUpdateData>,
+) -> GatewayPresenceUpdateData {
+    GatewayPresenceUpdateData {
+        since: None,
+        activities: activity.into_iter().collect(),
+        status,
+        afk: false,
+    }
+}
+
+/// Writer of the shard's current connection.
+#[derive(Clone, Debug)]
+struct ShardWriter {
+    tx: mpsc::Sender<Message>,
+    encoding: GatewayEncoding,
+}
+
+/// Shared view of one running shard; outlives reconnects.
+#[derive(Debug)]
+pub struct ShardHandle {
+    id: ShardId,
+    latency: Mutex<Option<Duration>>,
+    zombies: AtomicU64,
+    writer: Mutex<Option<ShardWriter>>,
+    presence: Mutex<Option<GatewayPresenceUpdateData>>,
+    members: MemberChunks,
+    state: watch::Sender<ShardState>,
+}
+
+impl ShardHandle {
+    pub fn new(id: ShardId) -> Self {
+        Self {
+            id,
+            latency: Mutex::new(None),
+            zombies: AtomicU64::new(0),
+            writer: Mutex::new(None),
+            presence: Mutex::new(None),
+            members: MemberChunks::default(),
+            state: watch::Sender::new(ShardState::default()),
+        }
+    }
+
+    #[inline]
+    pub fn id(&self) -> ShardId {
+        self.id
+    }
+
+    /// Round-trip time of the last acknowledged heartbeat.
+    #[inline]
+    pub fn latency(&self) -> Option<Duration> {
+        *self.latency.lock().unwrap()
+    }
+
+    #[inline]
+    pub fn state(&self) -> ShardState {
+        *self.state.borrow()
+    }
+
+    /// Observes state transitions, e.g. to wait for `Ready`.
+    pub fn watch_state(&self) -> watch::Receiver<ShardState> {
+        self.state.subscribe()
+    }
+
+    pub(crate) fn set_state(&self, state: ShardState) {
+        self.state.send_replace(state);
+    }
+
+    pub fn stats(&self) -> ShardStats {
+        ShardStats {
+            shard_id: self.id,
+            state: self.state(),
+            latency: self.latency(),
+            zombie_reconnects: self.zombies.load(Ordering::Relaxed),
+            queued_commands: self.queued_commands(),
+        }
+    }
+
+    /// User commands waiting for the connection's rate limit.
+    pub fn queued_commands(&self) -> usize {
+        match &*self.writer.lock().unwrap() {
+            Some(writer) => writer.tx.max_capacity() - writer.tx.capacity(),
+            None => 0,
+        }
+    }
+
+    pub(crate) fn record_latency(&self, rtt: Duration) {
+        *self.latency.lock().unwrap() = Some(rtt);
+    }
+
+    pub(crate) fn record_zombie(&self) {
+        self.zombies.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Last presence set on this shard; sent with every IDENTIFY.
+    pub fn presence(&self) -> Option<GatewayPresenceUpdateData> {
+        self.presence.lock().unwrap().clone()
+    }
+
+    /// Sets the bot's status and activity on this shard.
+    pub fn set_presence(
+        &self,
+        status: PresenceUpdateStatus,
+        activity: Option<GatewayActivityUpdateData>,
+    ) -> anyhow::Result<()> {
+        self.update_presence(presence_update(status, activity))
+    }
+
+    /// Sends op 3 and remembers `presence` for later sessions.
+    ///
+    /// While the shard is (re)connecting the update is only stored; the next
+    /// IDENTIFY or RESUME applies it.
+    pub fn update_presence(&self, presence: GatewayPresenceUpdateData) -> anyhow::Result<()> {
+        let payload = serde_json::json!({ "op": 3, "d": &presence });
+        *self.presence.lock().unwrap() = Some(presence);
+        match self.send(&payload) {
+            Err(e) if matches!(e.downcast_ref(), Some(CommandError::NotConnected(_))) => Ok(()),
+            res => res,
+        }
+    }
+
+    /// Sends op 8 and collects every GUILD_MEMBERS_CHUNK of the reply.
+    ///
+    /// Listing all members (or using a query) requires the `GUILD_MEMBERS`
+    /// intent; `presences` requires `GUILD_PRESENCES`.
+    pub async fn request_guild_members(
+        &self,
+        guild_id: impl Into<String>,
+        filter: MemberFilter,
+        presences: bool,
+    ) -> anyhow::Result<GuildMembers> {
+        let guild_id = guild_id.into();
+        let (nonce, rx) = self.members.register(self.id, &guild_id);
+        let request = filter.into_request(guild_id, presences, nonce.clone());
+        if let Err(e) = self.send(&serde_json::json!({ "op": 8, "d": request })) {
+            self.members.cancel(&nonce);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(MEMBER_CHUNK_TIMEOUT, rx).await {
+            Ok(Ok(members)) => Ok(members),
+            Ok(Err(_)) => anyhow::bail!("member request {nonce} was dropped"),
+            Err(_) => {
+                self.members.cancel(&nonce);
+                anyhow::bail!("timed out waiting for GUILD_MEMBERS_CHUNK (nonce {nonce})")
+            }
+        }
+    }
+
+    /// Feeds GUILD_MEMBERS_CHUNK to pending member requests.
+    pub(crate) fn observe(&self, ev: &GatewayDispatch<Box<RawValue>>) {
+        if ev.t != GatewayDispatchEvents::GuildMembersChunk || !self.members.is_waiting() {
+            return;
+        }
+        match serde_json::from_str(ev.d.get()) {
+            Ok(chunk) => self.members.feed(chunk),
+            Err(e) => log!("WARN", "[shard {}] bad GUILD_MEMBERS_CHUNK: {e}", self.id),
+        }
+    }
+
+    /// Routes later commands to a new connection.
+    pub(crate) fn attach(&self, tx: mpsc::Sender<Message>, encoding: GatewayEncoding) {
+        *self.writer.lock().unwrap() = Some(ShardWriter { tx, encoding });
+    }
+
+    /// Queues a gateway command on the current connection, behind its rate limit.
+    pub(crate) fn send<T: Serialize>(&self, payload: &T) -> anyhow::Result<()> {
+        let Some(writer) = self.writer.lock().unwrap().clone() else {
+            return Err(CommandError::NotConnected(self.id).into());
+        };
+        let msg = writer.encoding.encode(payload)?;
+        writer.tx.try_send(msg).map_err(|e| match e {
+            TrySendError::Full(_) => CommandError::QueueFull(self.id),
+            TrySendError::Closed(_) => CommandError::NotConnected(self.id),
+        })?;
+        Ok(())
+    }
+}
+
+/// Why a gateway command was not queued.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    /// No open connection (not started yet, or reconnecting).
+    #[error("shard {0} is not connected")]
+    NotConnected(ShardId),
+    /// The rate limit is exhausted and the command queue is full.
+    #[error("shard {0} command queue is full")]
+    QueueFull(ShardId),
+}
+
+/// Point-in-time figures for one shard.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShardStats {
+    pub shard_id: ShardId,
+    pub state: ShardState,
+    /// Round-trip time of the last acknowledged heartbeat.
+    pub latency: Option<Duration>,
+    /// Connections dropped because a heartbeat went unacknowledged.
+    pub zombie_reconnects: u64,
+    /// User commands waiting for the gateway rate limit.
+    pub queued_commands: usize,
+}
+
+/// Every shard of this process, shared between the manager and [`Ctx`](crate::framework::context::Ctx).
+#[derive(Debug, Default)]
+pub struct ShardRegistry {
+    shards: RwLock<BTreeMap<ShardId, Arc<ShardHandle>>>,
+    total: AtomicU32,
+}
+
+impl ShardRegistry {
+    pub fn get(&self, id: ShardId) -> Option<Arc<ShardHandle>> {
+        self.shards.read().unwrap().get(&id).cloned()
+    }
+
+    /// Shard receiving events for `guild_id` (`(guild_id >> 22) % num_shards`),
+    /// if this process runs it.
+    pub fn for_guild(&self, guild_id: &str) -> Option<Arc<ShardHandle>> {
+        let total = self.total.load(Ordering::Relaxed).max(1) as u64;
+        let guild_id: u64 = guild_id.parse().ok()?;
+        self.get(((guild_id >> 22) % total) as ShardId)
+    }
+
+    /// Stats for every shard, ordered by id.
+    pub fn stats(&self) -> Vec<ShardStats> {
+        self.shards
+            .read()
+            .unwrap()
+            .values()
+            .map(|h| h.stats())
+            .collect()
+    }
+
+    /// Sets the bot's status and activity on every shard.
+    pub fn set_presence(
+        &self,
+        status: PresenceUpdateStatus,
+        activity: Option<GatewayActivityUpdateData>,
+    ) -> anyhow::Result<()> {
+        self.update_presence(presence_update(status, activity))
+    }
+
+    /// Sends `presence` on every shard.
+    ///
+    /// A shard that fails to send still stores it for its next session, and
+    /// the others are updated anyway; the first failure is returned.
+    pub fn update_presence(&self, presence: GatewayPresenceUpdateData) -> anyhow::Result<()> {
+        let shards: Vec<_> = self.shards.read().unwrap().values().cloned().collect();
+        let mut first_err = None;
+        for shard in shards {
+            if let Err(e) = shard.update_presence(presence.clone()) {
+                match first_err {
+                    None => first_err = Some(e),
+                    Some(_) => log!("WARN", "presence update failed: {e}"),
+                }
+            }
+        }
+        first_err.map_or(Ok(()), Err)
+    }
+
+    pub(crate) fn set_total(&self, total: ShardId) {
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    pub(crate) fn insert(&self, handle: Arc<ShardHandle>) {
+        self.shards.write().unwrap().insert(handle.id(), handle);
+    }
+}
+
+/// Spawns one gateway session per shard and merges their dispatches.
+pub struct ShardManager {
+    identify: Arc<IdentifyConfig>,
+    http: Arc<Http>,
+    range: ShardRange,
+    registry: Arc<ShardRegistry>,
+    identify_queue: Option<Arc<dyn IdentifyQueue>>,
+    encoding: GatewayEncoding,
+    transport_compression: bool,
+    presence: Option<GatewayPresenceUpdateData>,
+    gateway_url: Option<String>,
+    reconnect: ReconnectPolicy,
+    event_capacity: usize,
+    event_policies: EventPolicies,
+    event_stats: Arc<EventStats>,
+    session_store: Option<Arc<dyn SessionStore>>,
+    recorder: Option<Arc<Recorder>>,
+    proxy: Option<Proxy>,
+    shutdown_tx: watch::Sender<Option<CloseMode>>,
+    fatal_tx: mpsc::UnboundedSender<(ShardId, GatewayError)>,
+    fatal_rx: mpsc::UnboundedReceiver<(ShardId, GatewayError)>,
+    runners: Vec<JoinHandle<()>>,
+}
+
+impl ShardManager {
+    pub fn new(
+        identify: IdentifyConfig,
+        http: Arc<Http>,
+        range: ShardRange,
+        registry: Arc<ShardRegistry>,
+    ) -> Self {
+        let (shutdown_tx, _) = watch::channel(None);
+        let (fatal_tx, fatal_rx) = mpsc::unbounded_channel();
+        Self {
+            identify: Arc::new(identify),
+            http,
+            range,
+            registry,
+            identify_queue: None,
+            encoding: GatewayEncoding::default(),
+            transport_compression: cfg!(feature = "zlib-stream"),
+            presence: None,
+            gateway_url: None,
+            reconnect: ReconnectPolicy::default(),
+            event_capacity: DEFAULT_EVENT_CAPACITY,
+            event_policies: EventPolicies::default(),
+            event_stats: Arc::default(),
+            session_store: None,
+            recorder: None,
+            proxy: None,
+            shutdown_tx,
+            fatal_tx,
+            fatal_rx,
+            runners: vec![],
+        }
+    }
+
+    /// Replaces the in-process [`LocalIdentifyQueue`] (e.g. with a coordinator
+    /// shared by several processes).
+    pub fn identify_queue(&mut self, queue: Arc<dyn IdentifyQueue>) -> &mut Self {
+        self.identify_queue = Some(queue);
+        self
+    }
+
+    /// Payload encoding for every shard; defaults to JSON.
+    pub fn encoding(&mut self, encoding: GatewayEncoding) -> &mut Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Whether shards ask for `compress=zlib-stream`; defaults to on when the
+    /// `zlib-stream` feature is enabled.
+    pub fn transport_compression(&mut self, enabled: bool) -> &mut Self {
+        self.transport_compression = enabled;
+        self
+    }
+
+    /// Presence sent with the first IDENTIFY of every shard.
+    pub fn presence(&mut self, presence: GatewayPresenceUpdateData) -> &mut Self {
+        self.presence = Some(presence);
+        self
+    }
+
+    /// Connects to `url` instead of the URL returned by GET /gateway/bot.
+    pub fn gateway_url(&mut self, url: impl Into<String>) -> &mut Self {
+        self.gateway_url = Some(url.into());
+        self
+    }
+
+    /// Backoff applied when a shard fails to connect, resume or re-identify.
+    pub fn reconnect_policy(&mut self, policy: ReconnectPolicy) -> &mut Self {
+        self.reconnect = policy;
+        self
+    }
+
+    /// Dispatches buffered before the
+    /// [`OverflowPolicy`](crate::gateway::pipeline::OverflowPolicy) applies; defaults
+    /// to [`DEFAULT_EVENT_CAPACITY`].
+    pub fn event_capacity(&mut self, capacity: usize) -> &mut Self {
+        self.event_capacity = capacity.max(1);
+        self
+    }
+
+    /// What shards do with dispatches while the event queue is full.
+    pub fn event_policies(&mut self, policies: EventPolicies) -> &mut Self {
+        self.event_policies = policies;
+        self
+    }
+
+    /// Counters to update instead of the manager's own.
+    pub fn event_stats(&mut self, stats: Arc<EventStats>) -> &mut Self {
+        self.event_stats = stats;
+        self
+    }
+
+    /// Saves sessions on shutdown and resumes them on the next start.
+    pub fn session_store(&mut self, store: Arc<dyn SessionStore>) -> &mut Self {
+        self.session_store = Some(store);
+        self
+    }
+
+    /// Appends every received frame to `recorder`.
+    pub fn recorder(&mut self, recorder: Arc<Recorder>) -> &mut Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Opens the websockets through `proxy`.
+    pub fn proxy(&mut self, proxy: Proxy) -> &mut Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Resolves the shard range via GET /gateway/bot, spawns a runner per shard
+    /// and returns the merged dispatch stream.
+    pub async fn start(&mut self) -> anyhow::Result<EventReceiver> {
+        let info = self.http.get_gateway_bot().await?;
+
+        let (ids, total) = match &self.range {
+            ShardRange::Recommended => {
+                let total = info.shards.max(1) as ShardId;
+                (0..total, total)
+            }
+            ShardRange::Range { ids, total } => (ids.clone(), *total),
+        };
+        if ids.is_empty() || ids.end > total {
+            anyhow::bail!("invalid shard range {ids:?} of {total}");
+        }
+
+        let limit = &info.session_start_limit;
+        log!(
+            "GW",
+            "spawning {} shard(s) of {} (recommended={}, max_concurrency={}, session starts {}/{})",
+            ids.len(),
+            total,
+            info.shards,
+            limit.max_concurrency,
+            limit.remaining,
+            limit.total
+        );
+
+        let identify_queue = match &self.identify_queue {
+            Some(queue) => Arc::clone(queue),
+            None => Arc::new(LocalIdentifyQueue::new(limit)),
+        };
+
+        let config = ShardConfig {
+            gateway_url: self.gateway_url.clone().unwrap_or(info.base.url),
+            identify: Arc::clone(&self.identify),
+            identify_queue,
+            encoding: self.encoding,
+            transport_compression: self.transport_compression,
+            reconnect: self.reconnect.clone(),
+            session_store: self.session_store.clone(),
+            recorder: self.recorder.clone(),
+            proxy: self.proxy.clone(),
+        };
+        let (events_tx, events_rx) = pipeline::channel(
+            self.event_capacity,
+            self.event_policies.clone(),
+            Arc::clone(&self.event_stats),
+        );
+        self.registry.set_total(total);
+        for id in ids {
+            let handle = Arc::new(ShardHandle::new(id));
+            if let Some(presence) = &self.presence {
+                *handle.presence.lock().unwrap() = Some(presence.clone());
+            }
+            self.registry.insert(Arc::clone(&handle));
+            let shard = Shard::new(
+                handle,
+                total,
+                config.clone(),
+                events_tx.clone(),
+                self.shutdown_tx.subscribe(),
+            );
+            let fatal_tx = self.fatal_tx.clone();
+            // `run` drops the shard's sender; hold another one until the error
+            // is queued, so the event stream cannot end before it is reported.
+            let events_open = events_tx.clone();
+            self.runners.push(tokio::spawn(async move {
+                if let Err(err) = shard.run().await {
+                    log!("ERR", "[shard {id}] stopping: {err}");
+                    let _ = fatal_tx.send((id, err));
+                }
+                drop(events_open);
+            }));
+        }
+
+        Ok(events_rx)
+    }
+
+    /// Resolves when a shard stops on a fatal close code (bad token, intents, …).
+    pub async fn fatal(&mut self) -> (ShardId, GatewayError) {
+        // `fatal_tx` lives in `self`, so the channel never closes.
+        self.fatal_rx
+            .recv()
+            .await
+            .expect("fatal_tx is owned by the manager")
+    }
+
+    /// A fatal error already reported by a shard, without waiting.
+    pub fn try_fatal(&mut self) -> Option<(ShardId, GatewayError)> {
+        self.fatal_rx.try_recv().ok()
+    }
+
+    /// Closes every shard with `mode`; later calls keep the first mode.
+    pub fn shutdown(&self, mode: CloseMode) {
+        self.shutdown_tx.send_if_modified(|current| {
+            if current.is_some() {
+                return false;
+            }
+            *current = Some(mode);
+            true
+        });
+    }
+
+    /// Waits for every runner to exit.
+    pub async fn join(&mut self) {
+        for runner in self.runners.drain(..) {
+            let _ = runner.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presence_reaches_every_shard_despite_failures() {
+        let registry = ShardRegistry::default();
+
+        // Shard 0: its command queue is full, so sending fails.
+        let failing = Arc::new(ShardHandle::new(0));
+        let (full_tx, _full_rx) = mpsc::channel(1);
+        full_tx.try_send(Message::Text("queued".into())).unwrap();
+        failing.attach(full_tx, GatewayEncoding::Json);
+        // Shard 1: not connected, so the presence is only stored.
+        let disconnected = Arc::new(ShardHandle::new(1));
+        // Shard 2: connected.
+        let connected = Arc::new(ShardHandle::new(2));
+        let (tx, mut rx) = mpsc::channel(1);
+        connected.attach(tx, GatewayEncoding::Json);
+        for shard in [&failing, &disconnected, &connected] {
+            registry.insert(Arc::clone(shard));
+        }
+
+        let presence = presence_update(PresenceUpdateStatus::DoNotDisturb, None);
+        let err = registry.update_presence(presence.clone()).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<CommandError>(),
+            Some(&CommandError::QueueFull(0))
+        );
+        for shard in [&failing, &disconnected, &connected] {
+            assert_eq!(
+                shard.presence(),
+                Some(presence.clone()),
+                "shard {}",
+                shard.id()
+            );
+        }
+        assert!(rx.try_recv().is_ok(), "connected shard sent op 3");
+    }
+}
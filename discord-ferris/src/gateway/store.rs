@@ -0,0 +1,77 @@
+//! Gateway sessions kept across process restarts.
+//!
+//! With a [`SessionStore`], shards close with code 4000 on shutdown, save
+//! their session and RESUME it on the next start, so a deploy does not lose
+//! the dispatches sent while the process was down.
+
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::gateway::shard::ShardId;
+
+/// Everything needed to RESUME a shard's session.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub session_id: String,
+    pub resume_gateway_url: String,
+    /// Last sequence number received.
+    pub seq: Option<i64>,
+}
+
+/// Saves sessions on shutdown and hands them back on startup.
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    /// Session saved for `shard_id`, if any.
+    async fn load(&self, shard_id: ShardId) -> anyhow::Result<Option<SessionInfo>>;
+    async fn save(&self, shard_id: ShardId, session: &SessionInfo) -> anyhow::Result<()>;
+    /// Forgets `shard_id`'s session, e.g. once it can no longer be resumed.
+    async fn remove(&self, shard_id: ShardId) -> anyhow::Result<()>;
+}
+
+/// Keeps one `shard-<id>.json` file per shard in a directory.
+#[derive(Clone, Debug)]
+pub struct FileSessionStore {
+    dir: PathBuf,
+}
+
+impl FileSessionStore {
+    /// Stores sessions in `dir`, created on first save.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, shard_id: ShardId) -> PathBuf {
+        self.dir.join(format!("shard-{shard_id}.json"))
+    }
+}
+
+#[async_trait]
+impl SessionStore for FileSessionStore {
+    async fn load(&self, shard_id: ShardId) -> anyhow::Result<Option<SessionInfo>> {
+        let bytes = match tokio::fs::read(self.path(shard_id)).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(serde_json::from_slice(&bytes)?))
+    }
+
+    async fn save(&self, shard_id: ShardId, session: &SessionInfo) -> anyhow::Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        // Write then rename, so a crash never leaves half a file behind.
+        let path = self.path(shard_id);
+        let tmp = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, serde_json::to_vec_pretty(session)?).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, shard_id: ShardId) -> anyhow::Result<()> {
+        match tokio::fs::remove_file(self.path(shard_id)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
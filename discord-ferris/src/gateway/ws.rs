@@ -1,51 +1,57 @@
+use futures_util::stream::{SplitSink, SplitStream};
 use futures_util::{SinkExt, StreamExt};
 use rustls::{ClientConfig, RootCertStore};
-use serde::Deserialize;
-use serde_json::value::RawValue;
 use std::sync::Arc;
-use tokio::sync::{mpsc, watch};
-use tokio_tungstenite::{Connector, connect_async_tls_with_config, tungstenite::protocol::Message};
-
-use crate::gateway::Gateway;
-use crate::log;
-use crate::models::gateway::{
-    GatewayDispatch, GatewayDispatchEvents as GwEvt, GatewayIntents, GatewayOpcodes,
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, frame::coding::CloseCode};
+use tokio_tungstenite::{
+    Connector, MaybeTlsStream, WebSocketStream, client_async_tls_with_config,
+    connect_async_tls_with_config, tungstenite::Bytes, tungstenite::protocol::Message,
 };
 
-const DISCORD_GATEWAY_URL: &str = "wss://gateway.discord.gg/?v=10&encoding=json";
-
-#[derive(thiserror::Error, Debug)]
-pub enum ResumeError {
-    #[error("invalid session on resume (resumable={resumable})")]
-    InvalidSession { resumable: bool },
-    #[error(transparent)]
-    Transport(#[from] anyhow::Error),
-}
-
-#[derive(Deserialize)]
-struct FrameBorrowed<'a> {
-    op: i64,
-    #[serde(default)]
-    s: Option<i64>,
-    #[serde(default)]
-    t: Option<&'a str>,
-    /// Borrowed raw payload slice.
-    #[serde(borrow)]
-    d: Option<&'a RawValue>,
-}
+use crate::gateway::compression::{GatewayCompression, Inflater};
+use crate::gateway::encoding::{Frame, GatewayEncoding};
+use crate::gateway::ratelimit::{COMMAND_QUEUE_CAPACITY, CommandBucket};
+use crate::gateway::shard::ShardId;
+use crate::http::proxy::{Proxy, split_host_port};
+use crate::log;
 
-#[derive(Deserialize)]
-struct HelloD {
-    heartbeat_interval: u64,
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+type WsRead = SplitStream<WsStream>;
+type WsWrite = SplitSink<WsStream, Message>;
+
+pub(crate) const DISCORD_GATEWAY_URL: &str = "wss://gateway.discord.gg";
+
+/// One open websocket: decoded reads plus the queues of its writer task.
+pub(crate) struct Connection {
+    read: WsRead,
+    inflater: Inflater,
+    encoding: GatewayEncoding,
+    /// Library frames (heartbeats, IDENTIFY, RESUME, close); never rate limited.
+    pub writer_tx: mpsc::UnboundedSender<Message>,
+    /// User commands, sent behind the connection's rate limit.
+    pub commands_tx: mpsc::Sender<Message>,
 }
 
-fn map_event(name: &str) -> Option<GwEvt> {
-    serde_json::from_str::<GwEvt>(&format!("\"{name}\"")).ok()
+/// What the socket produced next.
+pub(crate) enum Received {
+    Payload(Frame),
+    /// Close frame (or end of stream, without a code).
+    Closed {
+        code: Option<u16>,
+    },
 }
 
-pub async fn connect(token: &str, intents: GatewayIntents) -> anyhow::Result<Gateway> {
-    log!("GW", "connecting to discord gateway");
-
+/// Opens a websocket to `base`, through `proxy` if given, and spawns its
+/// writer task.
+pub(crate) async fn open(
+    base: &str,
+    encoding: GatewayEncoding,
+    compression: GatewayCompression,
+    proxy: Option<&Proxy>,
+    shard_id: ShardId,
+) -> anyhow::Result<Connection> {
     // TLS
     let provider = rustls::crypto::ring::default_provider().into();
     let mut root_store = RootCertStore::empty();
@@ -59,433 +65,151 @@ pub async fn connect(token: &str, intents: GatewayIntents) -> anyhow::Result<Gat
     let connector = Connector::Rustls(Arc::new(config));
 
     // WS
-    let (ws_stream, _) =
-        connect_async_tls_with_config(DISCORD_GATEWAY_URL, None, true, Some(connector)).await?;
-    log!("OK", "connection established");
-    let (mut write, mut read) = ws_stream.split();
-
-    // Single writer task
-    let (writer_tx, mut writer_rx) = mpsc::unbounded_channel::<Message>();
-    tokio::spawn(async move {
-        while let Some(msg) = writer_rx.recv().await {
-            if let Err(e) = write.send(msg).await {
-                log!("ERR", "[writer] send error: {e}");
-                break;
-            }
-        }
-    });
-
-    // Aux channels
-    let (immediate_tx, immediate_rx) = mpsc::channel::<()>(8);
-    let (last_seq_tx, last_seq_rx) = watch::channel::<Option<i64>>(None);
-    let (shutdown_tx, shutdown_rx) = watch::channel(false);
-
-    // 1) HELLO
-    let heartbeat_interval_ms = loop {
-        let Some(frame) = read.next().await else {
-            let _ = shutdown_tx.send(true);
-            anyhow::bail!("gateway closed before HELLO");
-        };
-        let Message::Text(text) = frame? else {
-            continue;
-        };
-        let Ok(f) = serde_json::from_str::<FrameBorrowed>(&text) else {
-            continue;
-        };
-        if f.op == 10 {
-            let d = f.d.ok_or_else(|| anyhow::anyhow!("HELLO without d"))?;
-            let hello = serde_json::from_str::<HelloD>(d.get())?;
-            log!(
-                "OK",
-                "received HELLO, heartbeat_interval={}ms",
-                hello.heartbeat_interval
-            );
-            break hello.heartbeat_interval;
+    let url = normalize_gateway_url(base, encoding, compression);
+    let (ws_stream, _) = match proxy {
+        Some(proxy) => {
+            let (host, port) = gateway_host_port(&url)?;
+            let stream = proxy.connect(&host, port).await?;
+            client_async_tls_with_config(url, stream, None, Some(connector)).await?
         }
+        None => connect_async_tls_with_config(url, None, true, Some(connector)).await?,
     };
+    log!("OK", "[shard {shard_id}] connection established");
+    let (write, read) = ws_stream.split();
 
-    // 2) heartbeat
-    tokio::spawn(crate::gateway::heartbeat::run_heartbeat(
-        writer_tx.clone(),
-        heartbeat_interval_ms,
-        immediate_rx,
-        last_seq_rx.clone(),
-        shutdown_rx,
-    ));
-
-    // 3) IDENTIFY
-    let identify = serde_json::json!({
-        "op": 2,
-        "d": {
-            "token": token,
-            "intents": intents.bits(),
-            "properties": { "os": "linux", "browser": "discord-ferris", "device": "discord-ferris" }
-        }
-    });
-    writer_tx.send(Message::Text(identify.to_string().into()))?;
-    log!("GW", "payload op2 (identify) queued");
-
-    // Dispatch channel (raw `d`)
-    let (events_tx, events_rx) = mpsc::unbounded_channel::<GatewayDispatch<Box<RawValue>>>();
-
-    // 4) Read until READY
-    let mut session_id: Option<String> = None;
-    let mut resume_gateway_url: Option<String> = None;
-
-    loop {
-        let Some(msg) = read.next().await else {
-            let _ = shutdown_tx.send(true);
-            anyhow::bail!("gateway closed before READY");
-        };
-        let Message::Text(text) = msg? else { continue };
-        let Ok(f) = serde_json::from_str::<FrameBorrowed>(&text) else {
-            continue;
-        };
-
-        if let Some(s) = f.s {
-            let _ = last_seq_tx.send_replace(Some(s));
-        }
+    // Single writer task
+    let (writer_tx, writer_rx) = mpsc::unbounded_channel::<Message>();
+    let (commands_tx, commands_rx) = mpsc::channel::<Message>(COMMAND_QUEUE_CAPACITY);
+    tokio::spawn(run_writer(write, writer_rx, commands_rx, shard_id));
+
+    Ok(Connection {
+        read,
+        inflater: Inflater::new(compression),
+        encoding,
+        writer_tx,
+        commands_tx,
+    })
+}
 
-        match f.op {
-            0 => {
-                let Some(t) = f.t else { continue };
-                if t == "READY" {
-                    if let Some(d) = f.d {
-                        #[derive(Deserialize)]
-                        struct ReadyD<'a> {
-                            session_id: &'a str,
-                            #[serde(default)]
-                            resume_gateway_url: Option<&'a str>,
-                        }
-                        if let Ok(r) = serde_json::from_str::<ReadyD>(d.get()) {
-                            session_id = Some(r.session_id.to_string());
-                            resume_gateway_url = Some(
-                                r.resume_gateway_url
-                                    .unwrap_or(DISCORD_GATEWAY_URL)
-                                    .to_string(),
-                            );
-                        }
-                    }
-                    log!("EVT", "READY");
-                    let ev = GatewayDispatch {
-                        op: GatewayOpcodes::Dispatch,
-                        t: GwEvt::Ready,
-                        s: f.s.unwrap_or(0),
-                        d: Box::from(RawValue::from_string(f.d.unwrap().get().to_owned()).unwrap()),
-                    };
-                    let _ = events_tx.send(ev);
-                    break;
-                } else if let Some(evt) = map_event(t) {
-                    let ev = GatewayDispatch {
-                        op: GatewayOpcodes::Dispatch,
-                        t: evt,
-                        s: f.s.unwrap_or(0),
-                        d: Box::from(RawValue::from_string(f.d.unwrap().get().to_owned()).unwrap()),
-                    };
-                    let _ = events_tx.send(ev);
+impl Connection {
+    /// Reads until a whole payload or a close arrives; undecodable payloads
+    /// are skipped.
+    pub async fn recv(&mut self) -> anyhow::Result<Received> {
+        loop {
+            let Some(msg) = self.read.next().await else {
+                return Ok(Received::Closed { code: None });
+            };
+            let payload = match msg? {
+                Message::Text(text) => Bytes::from(text),
+                Message::Binary(bytes) => match self.inflater.push(bytes)? {
+                    Some(payload) => payload,
+                    None => continue,
+                },
+                Message::Close(frame) => {
+                    let code = frame.map(|f| u16::from(f.code));
+                    return Ok(Received::Closed { code });
                 }
-            }
-            1 => {
-                let _ = immediate_tx.try_send(());
-            } // HEARTBEAT request
-            7 => {
-                log!("WARN", "[gw] RECONNECT requested");
-            }
-            9 => {
-                log!("WARN", "[gw] INVALID_SESSION: {:?}", f.d.map(|d| d.get()));
-            }
-            11 => {}
-            _ => {}
-        }
-    }
-
-    let session_id = session_id.unwrap_or_default();
-    let resume_gateway_url = resume_gateway_url.unwrap_or_else(|| DISCORD_GATEWAY_URL.to_string());
-
-    // Background reader after READY
-    let events_tx_bg = events_tx.clone();
-    let last_seq_tx_bg = last_seq_tx.clone();
-    let immediate_tx_bg = immediate_tx.clone();
-    tokio::spawn(async move {
-        while let Some(msg) = read.next().await {
-            let Ok(Message::Text(text)) = msg else {
-                continue;
+                _ => continue,
             };
-            let Ok(f) = serde_json::from_str::<FrameBorrowed>(&text) else {
+            let Ok(frame) = self.encoding.decode(&payload) else {
                 continue;
             };
-
-            if let Some(s) = f.s {
-                let _ = last_seq_tx_bg.send_replace(Some(s));
-            }
-
-            match f.op {
-                0 => {
-                    if let (Some(t), Some(d)) = (f.t, f.d) {
-                        if let Some(evt) = map_event(t) {
-                            let ev = GatewayDispatch {
-                                op: GatewayOpcodes::Dispatch,
-                                t: evt,
-                                s: f.s.unwrap_or(0),
-                                d: Box::from(RawValue::from_string(d.get().to_owned()).unwrap()),
-                            };
-                            let _ = events_tx_bg.send(ev);
-                        }
-                    }
-                }
-                1 => {
-                    let _ = immediate_tx_bg.try_send(());
-                }
-                7 => {
-                    log!("WARN", "[gw] RECONNECT requested");
-                }
-                9 => {
-                    log!("WARN", "[gw] INVALID_SESSION during running");
-                }
-                11 => {}
-                _ => {}
-            }
+            return Ok(Received::Payload(frame));
         }
-        log!("WARN", "[reader] stream closed");
-    });
-
-    Ok(Gateway {
-        session_id,
-        resume_gateway_url,
-        writer_tx,
-        events_rx,
-        last_seq_rx,
-        shutdown_tx,
-    })
-}
-
-pub async fn resume(
-    token: &str,
-    session_id: &str,
-    resume_gateway_url: &str,
-    last_seq: Option<i64>,
-) -> Result<Gateway, ResumeError> {
-    log!(
-        "GW",
-        "resuming session_id={} at {}",
-        session_id,
-        resume_gateway_url
-    );
-
-    // TLS
-    let provider = rustls::crypto::ring::default_provider().into();
-    let mut root_store = RootCertStore::empty();
-    root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
-
-    let config = ClientConfig::builder_with_provider(provider)
-        .with_safe_default_protocol_versions()
-        .map_err(|e| ResumeError::Transport(anyhow::anyhow!(e)))?
-        .with_root_certificates(root_store)
-        .with_no_client_auth();
-
-    let connector = Connector::Rustls(Arc::new(config));
-
-    // WS
-    let resume_url = normalize_gateway_url(resume_gateway_url);
-    let (ws_stream, _) = connect_async_tls_with_config(resume_url, None, true, Some(connector))
-        .await
-        .map_err(|e| ResumeError::Transport(anyhow::anyhow!(e)))?;
-    log!("OK", "reconnected websocket");
-    let (mut write, mut read) = ws_stream.split();
-
-    // Writer
-    let (writer_tx, mut writer_rx) = mpsc::unbounded_channel::<Message>();
-    tokio::spawn(async move {
-        while let Some(msg) = writer_rx.recv().await {
-            if let Err(e) = write.send(msg).await {
-                log!("ERR", "[writer] send error: {e}");
-                break;
-            }
-        }
-    });
-
-    // Aux channels
-    let (immediate_tx, immediate_rx) = mpsc::channel::<()>(8);
-    let (last_seq_tx, last_seq_rx) = watch::channel::<Option<i64>>(last_seq);
-    let (shutdown_tx, shutdown_rx) = watch::channel(false);
-
-    // HELLO
-    let heartbeat_interval_ms = loop {
-        let Some(frame) = read.next().await else {
-            let _ = shutdown_tx.send(true);
-            return Err(ResumeError::Transport(anyhow::anyhow!(
-                "gateway closed before HELLO (resume)"
-            )));
-        };
-        let Message::Text(text) = frame.map_err(|e| ResumeError::Transport(anyhow::anyhow!(e)))?
-        else {
-            continue;
-        };
-        let Ok(f) = serde_json::from_str::<FrameBorrowed>(&text) else {
-            continue;
-        };
-        if f.op == 10 {
-            let d =
-                f.d.ok_or_else(|| ResumeError::Transport(anyhow::anyhow!("HELLO without d")))?;
-            let hello = serde_json::from_str::<HelloD>(d.get())
-                .map_err(|e| ResumeError::Transport(anyhow::anyhow!(e)))?;
-            log!(
-                "OK",
-                "received HELLO (resume), heartbeat_interval={}ms",
-                hello.heartbeat_interval
-            );
-            break hello.heartbeat_interval;
-        }
-    };
+    }
 
-    // Heartbeat before RESUME
-    tokio::spawn(crate::gateway::heartbeat::run_heartbeat(
-        writer_tx.clone(),
-        heartbeat_interval_ms,
-        immediate_rx,
-        last_seq_rx.clone(),
-        shutdown_rx,
-    ));
+    /// Queues a library payload ahead of user commands.
+    pub fn send<T: serde::Serialize>(&self, payload: &T) -> anyhow::Result<()> {
+        self.writer_tx.send(self.encoding.encode(payload)?)?;
+        Ok(())
+    }
 
-    // RESUME
-    let resume_payload = serde_json::json!({
-        "op": 6,
-        "d": { "token": token, "session_id": session_id, "seq": last_seq }
-    });
-    writer_tx
-        .send(Message::Text(resume_payload.to_string().into()))
-        .map_err(|e| ResumeError::Transport(anyhow::anyhow!("failed to queue RESUME: {e}")))?;
-    log!("GW", "payload op6 (resume) queued with seq={last_seq:?}");
+    /// Closes with 4000 rather than 1000, which would invalidate the session.
+    pub fn close_resumable(&self, reason: &str) {
+        let _ = self.writer_tx.send(Message::Close(Some(CloseFrame {
+            code: CloseCode::Library(4000),
+            reason: reason.to_owned().into(),
+        })));
+    }
 
-    // Dispatch channel
-    let (events_tx, events_rx) = mpsc::unbounded_channel::<GatewayDispatch<Box<RawValue>>>();
+    /// Closes with 1000, ending the session.
+    pub fn close_normal(&self) {
+        let _ = self.writer_tx.send(Message::Close(Some(CloseFrame {
+            code: CloseCode::Normal,
+            reason: "shutdown".into(),
+        })));
+    }
+}
 
-    // Wait until RESUMED
+/// Writes frames for one connection.
+///
+/// Library frames (`writer_rx`) go out immediately; user commands
+/// (`commands_rx`) wait for a free slot in the connection's [`CommandBucket`].
+async fn run_writer(
+    mut write: WsWrite,
+    mut writer_rx: mpsc::UnboundedReceiver<Message>,
+    mut commands_rx: mpsc::Receiver<Message>,
+    shard_id: ShardId,
+) {
+    let mut bucket = CommandBucket::new();
+    let mut commands_open = true;
     loop {
-        let Some(msg) = read.next().await else {
-            let _ = shutdown_tx.send(true);
-            return Err(ResumeError::Transport(anyhow::anyhow!(
-                "gateway closed before RESUMED"
-            )));
-        };
-        let Message::Text(text) = msg.map_err(|e| ResumeError::Transport(anyhow::anyhow!(e)))?
-        else {
-            continue;
-        };
-        let Ok(f) = serde_json::from_str::<FrameBorrowed>(&text) else {
-            continue;
-        };
-
-        if let Some(s) = f.s {
-            let _ = last_seq_tx.send_replace(Some(s));
-        }
-
-        match f.op {
-            0 => {
-                if let Some("RESUMED") = f.t {
-                    log!("EVT", "RESUMED");
-                    let ev = GatewayDispatch {
-                        op: GatewayOpcodes::Dispatch,
-                        t: GwEvt::Resumed,
-                        s: f.s.unwrap_or(0),
-                        d: Box::from(RawValue::from_string(f.d.unwrap().get().to_owned()).unwrap()),
-                    };
-                    let _ = events_tx.send(ev);
-                    break;
-                } else if let (Some(t), Some(d)) = (f.t, f.d) {
-                    if let Some(evt) = map_event(t) {
-                        let ev = GatewayDispatch {
-                            op: GatewayOpcodes::Dispatch,
-                            t: evt,
-                            s: f.s.unwrap_or(0),
-                            d: Box::from(RawValue::from_string(d.get().to_owned()).unwrap()),
-                        };
-                        let _ = events_tx.send(ev);
-                    }
+        bucket.refresh();
+        let user_slot = bucket.has_user_slot();
+        let msg = tokio::select! {
+            biased;
+            msg = writer_rx.recv() => match msg {
+                Some(msg) => msg,
+                None => break,
+            },
+            msg = commands_rx.recv(), if commands_open && user_slot => match msg {
+                Some(msg) => msg,
+                None => {
+                    commands_open = false;
+                    continue;
                 }
+            },
+            _ = tokio::time::sleep_until(bucket.reset_at()), if !user_slot => continue,
+        };
+        // Only payloads count towards the limit, not control frames.
+        if matches!(msg, Message::Text(_) | Message::Binary(_)) {
+            bucket.take();
+            if user_slot && !bucket.has_user_slot() {
+                log!(
+                    "WARN",
+                    "[shard {shard_id}] gateway command limit reached; queueing commands for {:?}",
+                    bucket.reset_at() - tokio::time::Instant::now()
+                );
             }
-            1 => {
-                let _ = immediate_tx.try_send(());
-            }
-            7 => {
-                log!("WARN", "[gw] RECONNECT requested during RESUME");
-            }
-            9 => {
-                let resumable =
-                    f.d.and_then(|v| v.get().parse::<bool>().ok())
-                        .unwrap_or(false);
-                log!("WARN", "[gw] INVALID_SESSION during RESUME: {resumable}");
-                let _ = shutdown_tx.send(true);
-                return Err(ResumeError::InvalidSession { resumable });
-            }
-            11 => {}
-            _ => {}
         }
-    }
-
-    // Background reader after RESUMED
-    let events_tx_bg = events_tx.clone();
-    let last_seq_tx_bg = last_seq_tx.clone();
-    let immediate_tx_bg = immediate_tx.clone();
-    tokio::spawn(async move {
-        while let Some(msg) = read.next().await {
-            let Ok(Message::Text(text)) = msg else {
-                continue;
-            };
-            let Ok(f) = serde_json::from_str::<FrameBorrowed>(&text) else {
-                continue;
-            };
-
-            if let Some(s) = f.s {
-                let _ = last_seq_tx_bg.send_replace(Some(s));
-            }
-
-            match f.op {
-                0 => {
-                    if let (Some(t), Some(d)) = (f.t, f.d) {
-                        if let Some(evt) = map_event(t) {
-                            let ev = GatewayDispatch {
-                                op: GatewayOpcodes::Dispatch,
-                                t: evt,
-                                s: f.s.unwrap_or(0),
-                                d: Box::from(RawValue::from_string(d.get().to_owned()).unwrap()),
-                            };
-                            let _ = events_tx_bg.send(ev);
-                        }
-                    }
-                }
-                1 => {
-                    let _ = immediate_tx_bg.try_send(());
-                }
-                7 => {
-                    log!("WARN", "[gw] RECONNECT requested");
-                }
-                9 => {
-                    log!("WARN", "[gw] INVALID_SESSION during running");
-                }
-                11 => {}
-                _ => {}
-            }
+        if let Err(e) = write.send(msg).await {
+            log!("ERR", "[writer] send error: {e}");
+            break;
         }
-        log!("WARN", "[reader] stream closed (resumed)");
-    });
-
-    Ok(Gateway {
-        session_id: session_id.to_string(),
-        resume_gateway_url: resume_gateway_url.to_string(),
-        writer_tx,
-        events_rx,
-        last_seq_rx,
-        shutdown_tx,
-    })
+    }
 }
 
-fn normalize_gateway_url(base: &str) -> String {
-    if base.contains('?') {
-        base.to_string()
-    } else if base.ends_with('/') {
-        format!("{base}?v=10&encoding=json")
+/// Host and port the websocket connects to (443 unless given).
+fn gateway_host_port(url: &str) -> anyhow::Result<(String, u16)> {
+    let (scheme, rest) = url.split_once("://").unwrap_or(("wss", url));
+    let authority = rest.split(['/', '?']).next().unwrap_or(rest);
+    let default_port = if scheme.eq_ignore_ascii_case("ws") {
+        80
     } else {
-        format!("{base}/?v=10&encoding=json")
-    }
+        443
+    };
+    split_host_port(authority, default_port)
+}
+
+fn normalize_gateway_url(
+    base: &str,
+    encoding: GatewayEncoding,
+    compression: GatewayCompression,
+) -> String {
+    let base = base.split('?').next().unwrap_or(base).trim_end_matches('/');
+    format!(
+        "{base}/?v=10&encoding={}{}",
+        encoding.as_str(),
+        compression.query()
+    )
 }
@@ -0,0 +1,296 @@
+//! Files uploaded with a request as `multipart/form-data`.
+//!
+//! The JSON body goes into the `payload_json` part and file `n` into
+//! `files[n]`; each file gets an entry with id `n` in the body's
+//! `attachments` array so Discord can match its filename and description.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use super::HttpError;
+pub use crate::models::rest::form_data::{Attachment, FormDataBody};
+
+/// Reads `file`, naming it in the error if that fails.
+pub(crate) async fn read(file: Attachment) -> Result<(String, Option<String>, Vec<u8>), HttpError> {
+    let (filename, description, data) = file.read().await;
+    match data {
+        Ok(data) => Ok((filename, description, data)),
+        Err(source) => Err(HttpError::Attachment { filename, source }),
+    }
+}
+
+impl<T: Serialize> FormDataBody<T> {
+    /// Reads the files and encodes everything as one multipart body.
+    pub(crate) async fn into_form(self) -> Result<Form, HttpError> {
+        let mut files = Vec::with_capacity(self.files.len());
+        for file in self.files {
+            files.push(read(file).await?);
+        }
+        let mut payload = serde_json::to_value(&self.payload_json)?;
+        link_attachments(&mut payload, &files);
+
+        let mut form = Form::new();
+        form.json("payload_json", &payload);
+        for (n, (filename, _, data)) in files.iter().enumerate() {
+            form.file(&format!("files[{n}]"), filename, data);
+        }
+        Ok(form)
+    }
+}
+
+/// Adds an `attachments` entry with id `n` for every `files[n]`, filling in
+/// the filename and description of entries the caller already listed.
+fn link_attachments(payload: &mut Value, files: &[(String, Option<String>, Vec<u8>)]) {
+    if files.is_empty() {
+        return;
+    }
+    // Interaction callbacks carry the message one level down.
+    let message = match payload.get_mut("data") {
+        Some(data) if data.is_object() => data,
+        _ => payload,
+    };
+    let Some(message) = message.as_object_mut() else {
+        return;
+    };
+    let attachments = message
+        .entry("attachments")
+        .or_insert_with(|| Value::Array(vec![]));
+    if attachments.is_null() {
+        *attachments = Value::Array(vec![]);
+    }
+    let Some(attachments) = attachments.as_array_mut() else {
+        return;
+    };
+    for (n, (filename, description, _)) in files.iter().enumerate() {
+        let index = attachments
+            .iter()
+            .position(|a| a.get("id").and_then(Value::as_u64) == Some(n as u64))
+            .unwrap_or_else(|| {
+                attachments.push(json!({ "id": n }));
+                attachments.len() - 1
+            });
+        let Some(entry) = attachments[index].as_object_mut() else {
+            continue;
+        };
+        entry.entry("filename").or_insert_with(|| json!(filename));
+        if let Some(description) = description {
+            entry
+                .entry("description")
+                .or_insert_with(|| json!(description));
+        }
+    }
+}
+
+/// An encoded `multipart/form-data` body, cheap to resend on a retry.
+#[derive(Debug)]
+pub(crate) struct Form {
+    boundary: String,
+    body: Vec<u8>,
+}
+
+impl Form {
+    pub(crate) fn new() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        Self {
+            boundary: format!("discord-ferris-{nanos:x}-{n:x}"),
+            body: vec![],
+        }
+    }
+
+    pub(crate) fn text(&mut self, name: &str, value: &str) {
+        self.part(name, None, None, value.as_bytes());
+    }
+
+    pub(crate) fn json(&mut self, name: &str, value: &Value) {
+        self.part(
+            name,
+            None,
+            Some("application/json"),
+            value.to_string().as_bytes(),
+        );
+    }
+
+    pub(crate) fn file(&mut self, name: &str, filename: &str, data: &[u8]) {
+        self.part(name, Some(filename), Some(content_type(filename)), data);
+    }
+
+    fn part(
+        &mut self,
+        name: &str,
+        filename: Option<&str>,
+        content_type: Option<&str>,
+        data: &[u8],
+    ) {
+        let mut header = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"",
+            self.boundary,
+            quote(name)
+        );
+        if let Some(filename) = filename {
+            header.push_str(&format!("; filename=\"{}\"", quote(filename)));
+        }
+        if let Some(content_type) = content_type {
+            header.push_str(&format!("\r\nContent-Type: {content_type}"));
+        }
+        header.push_str("\r\n\r\n");
+        self.body.extend_from_slice(header.as_bytes());
+        self.body.extend_from_slice(data);
+        self.body.extend_from_slice(b"\r\n");
+    }
+
+    /// Adds the form as the body of `req`.
+    pub(crate) fn apply(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let mut body = self.body.clone();
+        body.extend_from_slice(format!("--{}--\r\n", self.boundary).as_bytes());
+        req.header(
+            reqwest::header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={}", self.boundary),
+        )
+        .body(body)
+    }
+}
+
+/// Escapes a form-data header parameter the way browsers do.
+fn quote(s: &str) -> String {
+    s.replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Content type Discord recognises for a file name; other files are generic.
+fn content_type(filename: &str) -> &'static str {
+    let ext = filename
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase());
+    match ext.as_deref() {
+        Some("png") | Some("apng") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("json") => "application/json",
+        Some("txt") => "text/plain",
+        Some("mp3") => "audio/mpeg",
+        Some("ogg") => "audio/ogg",
+        Some("mp4") => "video/mp4",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(names: &[&str]) -> Vec<(String, Option<String>, Vec<u8>)> {
+        names
+            .iter()
+            .map(|name| (name.to_string(), None, vec![]))
+            .collect()
+    }
+
+    #[test]
+    fn links_files_by_id() {
+        let mut payload = json!({
+            "content": "hi",
+            "attachments": [
+                { "id": 1, "description": "second" },
+                { "id": "123456789012345678" },
+            ],
+        });
+        let mut files = files(&["a.png", "b.png"]);
+        files[0].1 = Some("first".to_owned());
+        link_attachments(&mut payload, &files);
+        assert_eq!(
+            payload["attachments"],
+            json!([
+                { "id": 1, "description": "second", "filename": "b.png" },
+                { "id": "123456789012345678" },
+                { "id": 0, "filename": "a.png", "description": "first" },
+            ])
+        );
+    }
+
+    #[test]
+    fn keeps_caller_filenames() {
+        let mut payload = json!({ "attachments": [{ "id": 0, "filename": "renamed.png" }] });
+        link_attachments(&mut payload, &files(&["a.png"]));
+        assert_eq!(
+            payload["attachments"],
+            json!([{ "id": 0, "filename": "renamed.png" }])
+        );
+    }
+
+    #[test]
+    fn links_interaction_callback_data() {
+        let mut payload = json!({ "type": 4, "data": { "content": "hi" } });
+        link_attachments(&mut payload, &files(&["a.png"]));
+        assert_eq!(
+            payload,
+            json!({
+                "type": 4,
+                "data": { "content": "hi", "attachments": [{ "id": 0, "filename": "a.png" }] },
+            })
+        );
+    }
+
+    #[test]
+    fn replaces_null_attachments() {
+        let mut payload = json!({ "content": "hi", "attachments": null });
+        link_attachments(&mut payload, &files(&["a.png"]));
+        assert_eq!(
+            payload["attachments"],
+            json!([{ "id": 0, "filename": "a.png" }])
+        );
+    }
+
+    #[test]
+    fn leaves_payload_without_files() {
+        let mut payload = json!({ "content": "hi", "attachments": null });
+        link_attachments(&mut payload, &[]);
+        assert_eq!(payload, json!({ "content": "hi", "attachments": null }));
+    }
+
+    #[test]
+    fn quotes_header_parameters() {
+        assert_eq!(quote("a\"b\r\nc.png"), "a%22b%0D%0Ac.png");
+    }
+
+    #[test]
+    fn frames_parts_and_closes_with_boundary() {
+        let mut form = Form::new();
+        form.json("payload_json", &json!({ "content": "hi" }));
+        form.file("files[0]", "say \"hi\".txt", b"hello");
+        let request = form
+            .apply(reqwest::Client::new().post("http://localhost/"))
+            .build()
+            .unwrap();
+
+        let b = &form.boundary;
+        assert_eq!(
+            request.headers()[reqwest::header::CONTENT_TYPE],
+            format!("multipart/form-data; boundary={b}").as_str()
+        );
+        let body = request.body().and_then(|body| body.as_bytes()).unwrap();
+        assert_eq!(
+            std::str::from_utf8(body).unwrap(),
+            format!(
+                "--{b}\r\n\
+                 Content-Disposition: form-data; name=\"payload_json\"\r\n\
+                 Content-Type: application/json\r\n\r\n\
+                 {{\"content\":\"hi\"}}\r\n\
+                 --{b}\r\n\
+                 Content-Disposition: form-data; name=\"files[0]\"; filename=\"say %22hi%22.txt\"\r\n\
+                 Content-Type: text/plain\r\n\r\n\
+                 hello\r\n\
+                 --{b}--\r\n"
+            )
+        );
+    }
+}
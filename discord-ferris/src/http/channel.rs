@@ -0,0 +1,141 @@
+//! Channel message endpoints.
+
+use reqwest::Method;
+
+use super::{Http, HttpError, with_reason};
+use crate::models::payloads::APIMessage;
+use crate::models::rest::channel::{
+    RESTGetAPIChannelMessagesQuery, RESTPatchAPIChannelMessageFormDataBody,
+    RESTPatchAPIChannelMessageJSONBody, RESTPostAPIChannelMessageFormDataBody,
+    RESTPostAPIChannelMessageJSONBody, RESTPostAPIChannelMessagesBulkDeleteJSONBody,
+};
+
+impl Http {
+    /// GET /channels/{channel_id}/messages/{message_id}
+    pub async fn get_message(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+    ) -> Result<APIMessage, HttpError> {
+        let path = format!("/channels/{channel_id}/messages/{message_id}");
+        let resp = self.request(Method::GET, &path, |req| req).await?;
+        Ok(HttpError::check(resp).await?.json().await?)
+    }
+
+    /// GET /channels/{channel_id}/messages
+    /// Up to 100 messages around, before or after a message (50 by default).
+    pub async fn get_messages(
+        &self,
+        channel_id: &str,
+        query: &RESTGetAPIChannelMessagesQuery,
+    ) -> Result<Vec<APIMessage>, HttpError> {
+        let path = format!("/channels/{channel_id}/messages");
+        let resp = self
+            .request(Method::GET, &path, |req| req.query(query))
+            .await?;
+        Ok(HttpError::check(resp).await?.json().await?)
+    }
+
+    /// POST /channels/{channel_id}/messages
+    pub async fn create_message(
+        &self,
+        channel_id: &str,
+        body: &RESTPostAPIChannelMessageJSONBody,
+    ) -> Result<APIMessage, HttpError> {
+        let path = format!("/channels/{channel_id}/messages");
+        let resp = self
+            .request(Method::POST, &path, |req| req.json(body))
+            .await?;
+        Ok(HttpError::check(resp).await?.json().await?)
+    }
+
+    /// [`Http::create_message`] with files.
+    pub async fn create_message_with_files(
+        &self,
+        channel_id: &str,
+        body: RESTPostAPIChannelMessageFormDataBody,
+    ) -> Result<APIMessage, HttpError> {
+        let path = format!("/channels/{channel_id}/messages");
+        let form = body.into_form().await?;
+        let resp = self
+            .request(Method::POST, &path, |req| form.apply(req))
+            .await?;
+        Ok(HttpError::check(resp).await?.json().await?)
+    }
+
+    /// PATCH /channels/{channel_id}/messages/{message_id}
+    pub async fn edit_message(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        body: &RESTPatchAPIChannelMessageJSONBody,
+    ) -> Result<APIMessage, HttpError> {
+        let path = format!("/channels/{channel_id}/messages/{message_id}");
+        let resp = self
+            .request(Method::PATCH, &path, |req| req.json(body))
+            .await?;
+        Ok(HttpError::check(resp).await?.json().await?)
+    }
+
+    /// [`Http::edit_message`] with files. List retained attachments in
+    /// `attachments` too, or the edit removes them.
+    pub async fn edit_message_with_files(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        body: RESTPatchAPIChannelMessageFormDataBody,
+    ) -> Result<APIMessage, HttpError> {
+        let path = format!("/channels/{channel_id}/messages/{message_id}");
+        let form = body.into_form().await?;
+        let resp = self
+            .request(Method::PATCH, &path, |req| form.apply(req))
+            .await?;
+        Ok(HttpError::check(resp).await?.json().await?)
+    }
+
+    /// DELETE /channels/{channel_id}/messages/{message_id}
+    /// `reason` ends up in the audit log.
+    pub async fn delete_message(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        reason: Option<&str>,
+    ) -> Result<(), HttpError> {
+        let path = format!("/channels/{channel_id}/messages/{message_id}");
+        let resp = self
+            .request(Method::DELETE, &path, |req| with_reason(req, reason))
+            .await?;
+        HttpError::check(resp).await?;
+        Ok(())
+    }
+
+    /// POST /channels/{channel_id}/messages/{message_id}/crosspost
+    /// Publishes a message of an announcement channel to its followers.
+    pub async fn crosspost_message(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+    ) -> Result<APIMessage, HttpError> {
+        let path = format!("/channels/{channel_id}/messages/{message_id}/crosspost");
+        let resp = self.request(Method::POST, &path, |req| req).await?;
+        Ok(HttpError::check(resp).await?.json().await?)
+    }
+
+    /// POST /channels/{channel_id}/messages/bulk-delete
+    /// Deletes 2–100 messages no older than two weeks.
+    pub async fn bulk_delete_messages(
+        &self,
+        channel_id: &str,
+        body: &RESTPostAPIChannelMessagesBulkDeleteJSONBody,
+        reason: Option<&str>,
+    ) -> Result<(), HttpError> {
+        let path = format!("/channels/{channel_id}/messages/bulk-delete");
+        let resp = self
+            .request(Method::POST, &path, |req| {
+                with_reason(req.json(body), reason)
+            })
+            .await?;
+        HttpError::check(resp).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,271 @@
+use reqwest::{Response, StatusCode};
+
+use crate::models::payloads::common::{
+    RESTError, RESTErrorData, RESTErrorGroupWrapper, RESTRateLimit,
+};
+use crate::models::rest::common::RESTJSONErrorCodes;
+
+/// One invalid field of a request, e.g. `embeds.0.title`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldError {
+    /// Dotted path to the field; empty for errors about the whole body.
+    pub path: String,
+    /// Discord's code for the problem, e.g. `BASE_TYPE_REQUIRED`.
+    pub code: String,
+    pub message: String,
+}
+
+/// Why a REST request failed.
+#[derive(thiserror::Error, Debug)]
+pub enum HttpError {
+    /// Discord rejected the request with a JSON error body.
+    #[error("discord http error {status}: {message} (code {raw_code})")]
+    Api {
+        status: StatusCode,
+        /// Parsed `code`; `None` for codes this version does not know.
+        code: Option<RESTJSONErrorCodes>,
+        raw_code: i32,
+        message: String,
+        /// Nested `errors`, flattened into one entry per field.
+        errors: Vec<FieldError>,
+    },
+    /// Still rate limited after every retry.
+    #[error("discord http error {status}: rate limited ({}, retry after {}s)", .limit.message, .limit.retry_after)]
+    RateLimited {
+        status: StatusCode,
+        limit: RESTRateLimit,
+    },
+    /// An error status without a JSON error body.
+    #[error("discord http error {status}: {body}")]
+    Status { status: StatusCode, body: String },
+    /// The request never got a usable response.
+    #[error("http transport error: {0}")]
+    Transport(#[from] reqwest::Error),
+    /// A file to upload could not be read; nothing was sent.
+    #[error("reading attachment {filename}: {source}")]
+    Attachment {
+        filename: String,
+        source: std::io::Error,
+    },
+    /// The body could not be encoded as JSON; nothing was sent.
+    #[error("encoding request body: {0}")]
+    Payload(#[from] serde_json::Error),
+}
+
+impl HttpError {
+    /// JSON error code, e.g. [`RESTJSONErrorCodes::UnknownMessage`].
+    pub fn code(&self) -> Option<RESTJSONErrorCodes> {
+        match self {
+            Self::Api { code, .. } => *code,
+            _ => None,
+        }
+    }
+
+    /// Status of the response, unless the request failed before one arrived.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Self::Api { status, .. }
+            | Self::RateLimited { status, .. }
+            | Self::Status { status, .. } => Some(*status),
+            Self::Transport(e) => e.status(),
+            Self::Attachment { .. } | Self::Payload(_) => None,
+        }
+    }
+
+    /// Turns an error response into an [`HttpError`]; passes successes through.
+    pub(crate) async fn check(resp: Response) -> Result<Response, Self> {
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(resp);
+        }
+        let body = resp.text().await?;
+        let limit = (status == StatusCode::TOO_MANY_REQUESTS)
+            .then(|| serde_json::from_str::<RESTRateLimit>(&body).ok())
+            .flatten();
+        if let Some(limit) = limit {
+            return Err(Self::RateLimited { status, limit });
+        }
+        let Ok(error) = serde_json::from_str::<RESTError>(&body) else {
+            return Err(Self::Status { status, body });
+        };
+        let code = serde_json::from_value(serde_json::json!(error.code)).ok();
+        let mut errors = vec![];
+        if let Some(data) = &error.errors {
+            flatten(data, &mut String::new(), &mut errors);
+        }
+        Err(Self::Api {
+            status,
+            code,
+            raw_code: error.code,
+            message: error.message,
+            errors,
+        })
+    }
+}
+
+/// Collects the leaves of Discord's nested `errors` object with their paths.
+fn flatten(data: &RESTErrorData, path: &mut String, out: &mut Vec<FieldError>) {
+    match data {
+        RESTErrorData::FieldInfo(info) => out.push(FieldError {
+            path: path.clone(),
+            code: info.code.clone(),
+            message: info.message.clone(),
+        }),
+        RESTErrorData::String(message) => out.push(FieldError {
+            path: path.clone(),
+            code: String::new(),
+            message: message.clone(),
+        }),
+        RESTErrorData::GroupWrapper(RESTErrorGroupWrapper { errors })
+        | RESTErrorData::List(errors) => {
+            for data in errors {
+                flatten(data, path, out);
+            }
+        }
+        RESTErrorData::Map(map) => {
+            // Sorted, so the order does not depend on the hash map.
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            for key in keys {
+                // `_errors` belongs to the map's own path.
+                if key == "_errors" {
+                    flatten(&map[key], path, out);
+                    continue;
+                }
+                let len = path.len();
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(key);
+                flatten(&map[key], path, out);
+                path.truncate(len);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field_errors(body: &str) -> Vec<FieldError> {
+        let error: RESTError = serde_json::from_str(body).unwrap();
+        assert_eq!(error.code, 50035);
+        let mut errors = vec![];
+        flatten(&error.errors.unwrap(), &mut String::new(), &mut errors);
+        errors
+    }
+
+    fn field(path: &str, code: &str, message: &str) -> FieldError {
+        FieldError {
+            path: path.to_owned(),
+            code: code.to_owned(),
+            message: message.to_owned(),
+        }
+    }
+
+    #[test]
+    fn flattens_nested_arrays_and_objects() {
+        let errors = field_errors(
+            r#"{
+                "code": 50035,
+                "message": "Invalid Form Body",
+                "errors": {
+                    "embeds": {
+                        "0": {
+                            "fields": {
+                                "1": {
+                                    "name": {"_errors": [{"code": "BASE_TYPE_REQUIRED", "message": "This field is required"}]}
+                                }
+                            },
+                            "title": {"_errors": [{"code": "BASE_TYPE_MAX_LENGTH", "message": "Must be 256 or fewer in length."}]}
+                        }
+                    },
+                    "components": {
+                        "0": {
+                            "components": {
+                                "1": {
+                                    "custom_id": {"_errors": [
+                                        {"code": "COMPONENT_CUSTOM_ID_DUPLICATED", "message": "Component custom id cannot be duplicated"},
+                                        {"code": "BASE_TYPE_MAX_LENGTH", "message": "Must be 100 or fewer in length."}
+                                    ]}
+                                }
+                            }
+                        }
+                    }
+                }
+            }"#,
+        );
+        assert_eq!(
+            errors,
+            [
+                field(
+                    "components.0.components.1.custom_id",
+                    "COMPONENT_CUSTOM_ID_DUPLICATED",
+                    "Component custom id cannot be duplicated",
+                ),
+                field(
+                    "components.0.components.1.custom_id",
+                    "BASE_TYPE_MAX_LENGTH",
+                    "Must be 100 or fewer in length.",
+                ),
+                field(
+                    "embeds.0.fields.1.name",
+                    "BASE_TYPE_REQUIRED",
+                    "This field is required",
+                ),
+                field(
+                    "embeds.0.title",
+                    "BASE_TYPE_MAX_LENGTH",
+                    "Must be 256 or fewer in length.",
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn root_errors_have_an_empty_path() {
+        let errors = field_errors(
+            r#"{
+                "code": 50035,
+                "message": "Invalid Form Body",
+                "errors": {"_errors": [{"code": "APPLICATION_COMMAND_TOO_LARGE", "message": "Command exceeds maximum size (8000)"}]}
+            }"#,
+        );
+        assert_eq!(
+            errors,
+            [field(
+                "",
+                "APPLICATION_COMMAND_TOO_LARGE",
+                "Command exceeds maximum size (8000)",
+            )]
+        );
+    }
+
+    #[test]
+    fn keeps_fields_next_to_root_errors() {
+        let errors = field_errors(
+            r#"{
+                "code": 50035,
+                "message": "Invalid Form Body",
+                "errors": {
+                    "_errors": [{"code": "MESSAGE_EMPTY", "message": "Cannot send an empty message"}],
+                    "content": {"_errors": [{"code": "BASE_TYPE_MAX_LENGTH", "message": "Must be 2000 or fewer in length."}]},
+                    "flags": {"_errors": ["Invalid flags"]}
+                }
+            }"#,
+        );
+        assert_eq!(
+            errors,
+            [
+                field("", "MESSAGE_EMPTY", "Cannot send an empty message"),
+                field(
+                    "content",
+                    "BASE_TYPE_MAX_LENGTH",
+                    "Must be 2000 or fewer in length.",
+                ),
+                field("flags", "", "Invalid flags"),
+            ]
+        );
+    }
+}
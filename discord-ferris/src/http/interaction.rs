@@ -0,0 +1,100 @@
+//! Interaction responses and followup messages.
+
+use reqwest::Method;
+
+use super::{Http, HttpError};
+use crate::models::payloads::APIMessage;
+use crate::models::rest::interactions::{
+    RESTPatchAPIInteractionOriginalResponseFormDataBody,
+    RESTPatchAPIInteractionOriginalResponseJSONBody, RESTPostAPIInteractionCallbackFormDataBody,
+    RESTPostAPIInteractionCallbackJSONBody, RESTPostAPIInteractionFollowupFormDataBody,
+    RESTPostAPIInteractionFollowupJSONBody,
+};
+use crate::models::rest::webhook::RESTPatchAPIWebhookWithTokenMessageQuery;
+
+impl Http {
+    /// POST /interactions/{interaction_id}/{interaction_token}/callback
+    pub async fn create_interaction_response(
+        &self,
+        interaction_id: &str,
+        token: &str,
+        body: &RESTPostAPIInteractionCallbackJSONBody,
+    ) -> Result<(), HttpError> {
+        let path = format!("/interactions/{interaction_id}/{token}/callback");
+        let resp = self
+            .request(Method::POST, &path, |req| req.json(body))
+            .await?;
+        HttpError::check(resp).await?;
+        Ok(())
+    }
+
+    /// [`Http::create_interaction_response`] with files; they attach to the
+    /// response's `data`.
+    pub async fn create_interaction_response_with_files(
+        &self,
+        interaction_id: &str,
+        token: &str,
+        body: RESTPostAPIInteractionCallbackFormDataBody,
+    ) -> Result<(), HttpError> {
+        let path = format!("/interactions/{interaction_id}/{token}/callback");
+        let form = body.into_form().await?;
+        let resp = self
+            .request(Method::POST, &path, |req| form.apply(req))
+            .await?;
+        HttpError::check(resp).await?;
+        Ok(())
+    }
+
+    /// PATCH /webhooks/{application_id}/{interaction_token}/messages/@original
+    pub async fn edit_original_interaction_response(
+        &self,
+        application_id: &str,
+        token: &str,
+        body: &RESTPatchAPIInteractionOriginalResponseJSONBody,
+    ) -> Result<APIMessage, HttpError> {
+        let query = RESTPatchAPIWebhookWithTokenMessageQuery::default();
+        self.edit_webhook_message(application_id, token, "@original", &query, body)
+            .await
+    }
+
+    /// [`Http::edit_original_interaction_response`] with files.
+    pub async fn edit_original_interaction_response_with_files(
+        &self,
+        application_id: &str,
+        token: &str,
+        body: RESTPatchAPIInteractionOriginalResponseFormDataBody,
+    ) -> Result<APIMessage, HttpError> {
+        let query = RESTPatchAPIWebhookWithTokenMessageQuery::default();
+        self.edit_webhook_message_with_files(application_id, token, "@original", &query, body)
+            .await
+    }
+
+    /// POST /webhooks/{application_id}/{interaction_token}
+    pub async fn create_followup_message(
+        &self,
+        application_id: &str,
+        token: &str,
+        body: &RESTPostAPIInteractionFollowupJSONBody,
+    ) -> Result<APIMessage, HttpError> {
+        let path = format!("/webhooks/{application_id}/{token}");
+        let resp = self
+            .request(Method::POST, &path, |req| req.json(body))
+            .await?;
+        Ok(HttpError::check(resp).await?.json().await?)
+    }
+
+    /// [`Http::create_followup_message`] with files.
+    pub async fn create_followup_message_with_files(
+        &self,
+        application_id: &str,
+        token: &str,
+        body: RESTPostAPIInteractionFollowupFormDataBody,
+    ) -> Result<APIMessage, HttpError> {
+        let path = format!("/webhooks/{application_id}/{token}");
+        let form = body.into_form().await?;
+        let resp = self
+            .request(Method::POST, &path, |req| form.apply(req))
+            .await?;
+        Ok(HttpError::check(resp).await?.json().await?)
+    }
+}
@@ -1,13 +1,33 @@
 use reqwest::Client as ReqClient;
 use reqwest::header::AUTHORIZATION;
+use reqwest::{Method, Response};
 use serde::Serialize;
 
-// Keep base URL as a constant; avoid storing it per-instance.
-const DISCORD_API_BASE: &str = "https://discord.com/api/v10";
+use crate::models::rest::RESTGetAPIGatewayBotResult;
+
+pub mod attachment;
+mod channel;
+mod error;
+mod interaction;
+pub mod proxy;
+pub mod ratelimit;
+mod reaction;
+mod sticker;
+mod webhook;
+pub use attachment::{Attachment, FormDataBody};
+pub use error::{FieldError, HttpError};
+use proxy::Proxy;
+use ratelimit::{RateLimiter, Route};
+pub use reaction::ReactionEmoji;
+
+/// REST base URL used unless another one is given.
+pub const DISCORD_API_BASE: &str = "https://discord.com/api/v10";
 
 pub struct Http {
     token: String,
+    base_url: String,
     client: ReqClient,
+    ratelimiter: RateLimiter,
 }
 
 // Payload types live at module scope (not inside impl).
@@ -36,16 +56,54 @@ struct AllowedMentions {
 
 impl Http {
     pub fn new(token: impl Into<String>) -> Self {
+        Self::with_base_url(token, DISCORD_API_BASE)
+    }
+
+    /// Sends requests to `base_url` (e.g. a REST proxy) instead of Discord.
+    pub fn with_base_url(token: impl Into<String>, base_url: impl Into<String>) -> Self {
+        Self::with_proxy(token, base_url, None).expect("failed to build HTTP Client")
+    }
+
+    /// Like [`Http::with_base_url`], sending every request through `proxy`.
+    ///
+    /// Fails if reqwest rejects the proxy, rather than connecting directly.
+    pub fn with_proxy(
+        token: impl Into<String>,
+        base_url: impl Into<String>,
+        proxy: Option<&Proxy>,
+    ) -> reqwest::Result<Self> {
         // One shared client; connection pooling by default.
-        let client = ReqClient::builder()
-            .user_agent("discord-ferris (github.com/andrewdotdev/discord-ferris)")
-            .build()
-            .expect("failed to build HTTP Client");
+        let mut builder = ReqClient::builder()
+            .user_agent("discord-ferris (github.com/andrewdotdev/discord-ferris)");
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(proxy.to_reqwest()?);
+        }
 
-        Self {
+        Ok(Self {
             token: token.into(),
-            client,
-        }
+            base_url: base_url.into().trim_end_matches('/').to_owned(),
+            client: builder.build()?,
+            ratelimiter: RateLimiter::default(),
+        })
+    }
+
+    /// Sends `method path` behind its rate-limit bucket; `build` adds the
+    /// body and runs again for every retry.
+    async fn request<F>(&self, method: Method, path: &str, build: F) -> reqwest::Result<Response>
+    where
+        F: Fn(reqwest::RequestBuilder) -> reqwest::RequestBuilder,
+    {
+        let route = Route::new(&method, path);
+        let url = format!("{}{path}", self.base_url);
+        self.ratelimiter
+            .send(&route, || {
+                build(
+                    self.client
+                        .request(method.clone(), &url)
+                        .header(AUTHORIZATION, format!("Bot {}", self.token)),
+                )
+            })
+            .await
     }
 
     /// POST /channels/{channel_id}/messages
@@ -56,8 +114,8 @@ impl Http {
         content: &str,
         reply_to: Option<&str>,
         mention_replied_user: bool,
-    ) -> anyhow::Result<()> {
-        let url = format!("{}/channels/{}/messages", DISCORD_API_BASE, channel_id);
+    ) -> Result<(), HttpError> {
+        let path = format!("/channels/{channel_id}/messages");
 
         let body = CreateMsg {
             content,
@@ -71,19 +129,38 @@ impl Http {
         };
 
         let resp = self
-            .client
-            .post(url)
-            .header(AUTHORIZATION, format!("Bot {}", self.token))
-            .json(&body)
-            .send()
+            .request(Method::POST, &path, |req| req.json(&body))
             .await?;
+        HttpError::check(resp).await?;
+        Ok(())
+    }
+
+    /// GET /gateway/bot
+    /// Recommended shard count and session start limits for this token.
+    pub async fn get_gateway_bot(&self) -> Result<RESTGetAPIGatewayBotResult, HttpError> {
+        let resp = self.request(Method::GET, "/gateway/bot", |req| req).await?;
+        Ok(HttpError::check(resp).await?.json().await?)
+    }
+}
+
+/// Adds `X-Audit-Log-Reason`, percent-encoded as Discord expects.
+fn with_reason(req: reqwest::RequestBuilder, reason: Option<&str>) -> reqwest::RequestBuilder {
+    match reason {
+        Some(reason) => req.header("X-Audit-Log-Reason", encode_component(reason)),
+        None => req,
+    }
+}
 
-        if resp.status().is_success() {
-            Ok(())
-        } else {
-            let status = resp.status();
-            let text = resp.text().await.unwrap_or_default();
-            Err(anyhow::anyhow!("discord http error {}: {}", status, text))
+/// Percent-encodes everything but RFC 3986 unreserved characters.
+pub(crate) fn encode_component(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{b:02X}")),
         }
     }
+    out
 }
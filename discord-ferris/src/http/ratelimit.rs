@@ -0,0 +1,427 @@
+//! REST rate limits, per route and major parameter.
+//!
+//! Requests wait in their bucket's queue until it has a request left. Buckets
+//! start out keyed by route and move to Discord's bucket hash once a response
+//! reveals it (`X-RateLimit-Bucket`), so routes sharing a limit share a queue.
+//! Idle buckets are dropped once their limit has reset.
+//!
+//! Every request but interaction callbacks also counts towards the global
+//! limit of [`GLOBAL_LIMIT`] requests per second.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use reqwest::header::HeaderMap;
+use reqwest::{Method, Response, StatusCode};
+use tokio::time::Instant;
+
+use crate::log;
+use crate::models::payloads::common::RESTRateLimit;
+
+/// Times a request is retried after a 429 before giving up.
+pub const MAX_RATE_LIMIT_RETRIES: u32 = 5;
+
+/// Requests per second allowed across all routes.
+pub const GLOBAL_LIMIT: u32 = 50;
+
+/// How often idle buckets are looked for.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Path segments after which a snowflake is a major parameter.
+const MAJOR_PARAMS: &[&str] = &["channels", "guilds", "webhooks"];
+
+/// Rate-limit bucket of one request.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Route {
+    /// Method and path with ids, tokens and emojis replaced, e.g.
+    /// `POST /channels/:id/messages`.
+    pub key: String,
+    /// `channels/123`, `guilds/456`, `webhooks/789/<token>` or empty.
+    pub major: String,
+}
+
+impl Route {
+    /// Route of `method path`, e.g. `POST /channels/123/messages`.
+    pub fn new(method: &Method, path: &str) -> Self {
+        let path = path.split('?').next().unwrap_or(path);
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        let mut key = vec![];
+        let mut major = String::new();
+        for (i, segment) in segments.iter().enumerate() {
+            let prev = i.checked_sub(1).map(|i| segments[i]);
+            let is_id = segment.bytes().all(|b| b.is_ascii_digit()) && !segment.is_empty();
+            if is_id && major.is_empty() && prev.is_some_and(|p| MAJOR_PARAMS.contains(&p)) {
+                major = format!("{}/{segment}", prev.unwrap_or_default());
+                key.push(":id");
+            } else if i >= 2 && segments[i - 2] == "webhooks" && major.starts_with("webhooks/") {
+                // Webhook token: part of the major parameter, never logged.
+                major.push('/');
+                major.push_str(segment);
+                key.push(":token");
+            } else if i >= 2 && segments[i - 2] == "interactions" {
+                // Interaction token: unique per interaction, never logged.
+                key.push(":token");
+            } else if is_id {
+                key.push(":id");
+            } else if prev == Some("reactions") && *segment != "@me" {
+                key.push(":emoji");
+            } else {
+                key.push(segment);
+            }
+        }
+        Self {
+            key: format!("{method} /{}", key.join("/")),
+            major,
+        }
+    }
+
+    /// Interaction callbacks are not bound by the global limit.
+    fn is_global(&self) -> bool {
+        !self.key.starts_with("POST /interactions/")
+    }
+}
+
+/// Remaining requests of one bucket.
+#[derive(Debug)]
+struct BucketState {
+    remaining: Option<u32>,
+    reset_at: Option<Instant>,
+}
+
+/// Requests of one bucket, served one at a time in order.
+#[derive(Debug)]
+struct Bucket {
+    state: tokio::sync::Mutex<BucketState>,
+}
+
+impl Bucket {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            state: tokio::sync::Mutex::new(BucketState {
+                remaining: None,
+                reset_at: None,
+            }),
+        })
+    }
+
+    /// Whether the bucket still limits anything at `now`.
+    fn is_active(&self, now: Instant) -> bool {
+        match self.state.try_lock() {
+            Ok(state) => state.reset_at.is_some_and(|reset| reset > now),
+            // A request is waiting on it.
+            Err(_) => true,
+        }
+    }
+}
+
+/// Requests sent in the current one-second window.
+#[derive(Debug)]
+struct GlobalWindow {
+    sent: u32,
+    reset_at: Instant,
+}
+
+/// Shared by every request of an [`Http`](super::Http).
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// Bucket hash learned for a route key.
+    hashes: Mutex<HashMap<String, String>>,
+    buckets: Mutex<HashMap<String, Arc<Bucket>>>,
+    next_sweep: Mutex<Instant>,
+    window: Mutex<GlobalWindow>,
+    /// Set while the global limit is exhausted.
+    global_reset: Mutex<Option<Instant>>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        let now = Instant::now();
+        Self {
+            hashes: Mutex::default(),
+            buckets: Mutex::default(),
+            next_sweep: Mutex::new(now + SWEEP_INTERVAL),
+            window: Mutex::new(GlobalWindow {
+                sent: 0,
+                reset_at: now,
+            }),
+            global_reset: Mutex::default(),
+        }
+    }
+}
+
+impl RateLimiter {
+    fn bucket(&self, route: &Route) -> Arc<Bucket> {
+        let id = match self.hashes.lock().unwrap().get(&route.key) {
+            Some(hash) => format!("{hash}:{}", route.major),
+            None => format!("{}:{}", route.key, route.major),
+        };
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let mut next_sweep = self.next_sweep.lock().unwrap();
+        if now >= *next_sweep {
+            *next_sweep = now + SWEEP_INTERVAL;
+            Self::sweep(&mut buckets, now);
+        }
+        Arc::clone(buckets.entry(id).or_insert_with(Bucket::new))
+    }
+
+    /// Drops buckets no request holds whose limit has reset.
+    fn sweep(buckets: &mut HashMap<String, Arc<Bucket>>, now: Instant) {
+        buckets.retain(|_, bucket| Arc::strong_count(bucket) > 1 || bucket.is_active(now));
+    }
+
+    /// Takes a slot of the global limit at `now`, or returns when to try again.
+    fn reserve_global(&self, now: Instant) -> Option<Instant> {
+        if let Some(reset) = self.global_reset.lock().unwrap().filter(|r| *r > now) {
+            return Some(reset);
+        }
+        let mut window = self.window.lock().unwrap();
+        if now >= window.reset_at {
+            window.sent = 0;
+            window.reset_at = now + Duration::from_secs(1);
+        }
+        if window.sent < GLOBAL_LIMIT {
+            window.sent += 1;
+            None
+        } else {
+            Some(window.reset_at)
+        }
+    }
+
+    async fn wait_global(&self, route: &Route) {
+        if !route.is_global() {
+            return;
+        }
+        while let Some(retry_at) = self.reserve_global(Instant::now()) {
+            tokio::time::sleep_until(retry_at).await;
+        }
+    }
+
+    /// Sends the request built by `build` once its bucket allows it, retrying
+    /// 429s after `retry_after`.
+    ///
+    /// `build` runs once per attempt, since a sent request cannot be reused.
+    pub async fn send<F>(&self, route: &Route, build: F) -> reqwest::Result<Response>
+    where
+        F: Fn() -> reqwest::RequestBuilder,
+    {
+        let mut retries = 0;
+        loop {
+            let bucket = self.bucket(route);
+            // Held until the headers are in, so the bucket's queue stays ordered.
+            let mut state = bucket.state.lock().await;
+            let exhausted = state.remaining == Some(0);
+            if let Some(reset) = state.reset_at.filter(|r| exhausted && *r > Instant::now()) {
+                log!(
+                    "HTTP",
+                    "bucket of {} exhausted; waiting {:?}",
+                    route.key,
+                    reset - Instant::now()
+                );
+                tokio::time::sleep_until(reset).await;
+            }
+            self.wait_global(route).await;
+
+            let resp = build().send().await?;
+            self.update(route, &mut state, resp.headers());
+            drop(state);
+
+            if resp.status() != StatusCode::TOO_MANY_REQUESTS || retries >= MAX_RATE_LIMIT_RETRIES {
+                return Ok(resp);
+            }
+            retries += 1;
+
+            let global = header::<String>(resp.headers(), "x-ratelimit-global").is_some();
+            let header_retry = header::<f64>(resp.headers(), "retry-after");
+            let body = resp.json::<RESTRateLimit>().await.ok();
+            let retry_after = body
+                .as_ref()
+                .map(|b| b.retry_after)
+                .or(header_retry)
+                .unwrap_or(1.0);
+            let wait = Duration::from_secs_f64(retry_after.max(0.0));
+            if global || body.as_ref().is_some_and(|b| b.global) {
+                *self.global_reset.lock().unwrap() = Some(Instant::now() + wait);
+            }
+            log!(
+                "WARN",
+                "429 on {} (global={global}); retry {retries}/{MAX_RATE_LIMIT_RETRIES} in {wait:?}",
+                route.key
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Records the limits a response reported for its bucket.
+    fn update(&self, route: &Route, state: &mut BucketState, headers: &HeaderMap) {
+        if let Some(remaining) = header::<u32>(headers, "x-ratelimit-remaining") {
+            state.remaining = Some(remaining);
+        }
+        if let Some(reset_after) = header::<f64>(headers, "x-ratelimit-reset-after") {
+            state.reset_at = Some(Instant::now() + Duration::from_secs_f64(reset_after.max(0.0)));
+        }
+        let Some(hash) = header::<String>(headers, "x-ratelimit-bucket") else {
+            return;
+        };
+        let mut hashes = self.hashes.lock().unwrap();
+        if hashes.get(&route.key) == Some(&hash) {
+            return;
+        }
+        // Move to the shared bucket, carrying over what we just learned.
+        let id = format!("{hash}:{}", route.major);
+        hashes.insert(route.key.clone(), hash);
+        self.buckets.lock().unwrap().entry(id).or_insert_with(|| {
+            Arc::new(Bucket {
+                state: tokio::sync::Mutex::new(BucketState {
+                    remaining: state.remaining,
+                    reset_at: state.reset_at,
+                }),
+            })
+        });
+    }
+}
+
+fn header<T: std::str::FromStr>(headers: &HeaderMap, name: &str) -> Option<T> {
+    headers.get(name)?.to_str().ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(method: Method, path: &str) -> (String, String) {
+        let route = Route::new(&method, path);
+        (route.key, route.major)
+    }
+
+    #[test]
+    fn masks_ids_and_keeps_the_major_parameter() {
+        assert_eq!(
+            route(Method::POST, "/channels/123/messages?wait=true"),
+            ("POST /channels/:id/messages".into(), "channels/123".into())
+        );
+        assert_eq!(
+            route(Method::GET, "/guilds/456/members/789"),
+            ("GET /guilds/:id/members/:id".into(), "guilds/456".into())
+        );
+        assert_eq!(
+            route(Method::GET, "/gateway/bot"),
+            ("GET /gateway/bot".into(), "".into())
+        );
+    }
+
+    #[test]
+    fn message_deletes_share_a_route_across_messages() {
+        let first = Route::new(&Method::DELETE, "/channels/123/messages/1");
+        let second = Route::new(&Method::DELETE, "/channels/123/messages/2");
+        assert_eq!(first, second);
+        assert_eq!(first.key, "DELETE /channels/:id/messages/:id");
+        assert_ne!(
+            first,
+            Route::new(&Method::DELETE, "/channels/456/messages/1")
+        );
+    }
+
+    #[test]
+    fn webhook_tokens_are_major_but_not_in_the_key() {
+        assert_eq!(
+            route(Method::POST, "/webhooks/789/secret-token"),
+            (
+                "POST /webhooks/:id/:token".into(),
+                "webhooks/789/secret-token".into()
+            )
+        );
+        assert_eq!(
+            route(Method::PATCH, "/webhooks/789/secret-token/messages/1"),
+            (
+                "PATCH /webhooks/:id/:token/messages/:id".into(),
+                "webhooks/789/secret-token".into()
+            )
+        );
+        assert_eq!(
+            route(Method::GET, "/webhooks/789"),
+            ("GET /webhooks/:id".into(), "webhooks/789".into())
+        );
+    }
+
+    #[test]
+    fn interaction_tokens_are_masked_and_skip_the_global_limit() {
+        let callback = Route::new(&Method::POST, "/interactions/123/secret-token/callback");
+        assert_eq!(callback.key, "POST /interactions/:id/:token/callback");
+        assert_eq!(callback.major, "");
+        assert!(!callback.is_global());
+        assert!(Route::new(&Method::POST, "/channels/1/messages").is_global());
+    }
+
+    #[test]
+    fn reaction_emojis_are_masked() {
+        assert_eq!(
+            route(
+                Method::PUT,
+                "/channels/1/messages/2/reactions/%F0%9F%91%8D/@me"
+            )
+            .0,
+            "PUT /channels/:id/messages/:id/reactions/:emoji/@me"
+        );
+        assert_eq!(
+            route(
+                Method::DELETE,
+                "/channels/1/messages/2/reactions/ferris:42/3"
+            )
+            .0,
+            "DELETE /channels/:id/messages/:id/reactions/:emoji/:id"
+        );
+        assert_eq!(
+            route(Method::GET, "/channels/1/messages/2/reactions/ferris:42").0,
+            "GET /channels/:id/messages/:id/reactions/:emoji"
+        );
+    }
+
+    #[test]
+    fn sweeps_idle_buckets_after_their_reset() {
+        let limiter = RateLimiter::default();
+        let now = Instant::now();
+        let held = limiter.bucket(&Route::new(&Method::GET, "/channels/1/messages"));
+        let limited = limiter.bucket(&Route::new(&Method::GET, "/channels/2/messages"));
+        limited.state.try_lock().unwrap().reset_at = Some(now + Duration::from_secs(5));
+        drop(limited);
+        limiter.bucket(&Route::new(&Method::GET, "/channels/3/messages"));
+
+        let mut buckets = limiter.buckets.lock().unwrap();
+        RateLimiter::sweep(&mut buckets, now);
+        let mut ids: Vec<_> = buckets.keys().cloned().collect();
+        ids.sort();
+        assert_eq!(
+            ids,
+            [
+                "GET /channels/:id/messages:channels/1",
+                "GET /channels/:id/messages:channels/2",
+            ]
+        );
+
+        RateLimiter::sweep(&mut buckets, now + Duration::from_secs(6));
+        assert_eq!(buckets.len(), 1);
+        drop(held);
+        RateLimiter::sweep(&mut buckets, now + Duration::from_secs(6));
+        assert!(buckets.is_empty());
+    }
+
+    #[test]
+    fn global_limit_allows_a_window_of_requests() {
+        let limiter = RateLimiter::default();
+        let now = Instant::now();
+        for _ in 0..GLOBAL_LIMIT {
+            assert_eq!(limiter.reserve_global(now), None);
+        }
+        let retry_at = limiter.reserve_global(now).unwrap();
+        assert_eq!(retry_at, now + Duration::from_secs(1));
+        assert_eq!(limiter.reserve_global(retry_at), None);
+
+        // A 429 with `global: true` holds every request until it resets.
+        let reset = retry_at + Duration::from_secs(3);
+        *limiter.global_reset.lock().unwrap() = Some(reset);
+        assert_eq!(limiter.reserve_global(retry_at), Some(reset));
+        assert_eq!(limiter.reserve_global(reset), None);
+    }
+}
@@ -0,0 +1,202 @@
+//! Message reaction endpoints.
+
+use reqwest::Method;
+
+use super::{Http, HttpError, encode_component};
+use crate::models::payloads::{APIEmoji, APIMessageComponentEmoji, APIPartialEmoji, APIUser};
+use crate::models::rest::channel::RESTGetAPIChannelMessageReactionUsersQuery;
+
+/// An emoji as it appears in reaction URLs: the percent-encoded character for
+/// unicode emoji, `name:id` for custom ones.
+pub trait ReactionEmoji {
+    fn to_path_segment(&self) -> String;
+}
+
+/// A unicode emoji (`"🔥"`) or a custom one as `name:id`, `a:name:id`,
+/// `<:name:id>` or `<a:name:id>`.
+impl ReactionEmoji for str {
+    fn to_path_segment(&self) -> String {
+        let emoji = match self.strip_prefix('<').and_then(|s| s.strip_suffix('>')) {
+            Some(inner) => inner
+                .strip_prefix(':')
+                .or_else(|| inner.strip_prefix("a:"))
+                .unwrap_or(inner),
+            // `a:` marks animated emoji, unless it is all there is before the id.
+            None => match self.strip_prefix("a:") {
+                Some(rest) if rest.contains(':') => rest,
+                _ => self,
+            },
+        };
+        match emoji.rsplit_once(':') {
+            Some((name, id)) => custom(Some(name), id),
+            None => encode_component(self),
+        }
+    }
+}
+
+impl ReactionEmoji for String {
+    fn to_path_segment(&self) -> String {
+        self.as_str().to_path_segment()
+    }
+}
+
+impl ReactionEmoji for APIPartialEmoji {
+    fn to_path_segment(&self) -> String {
+        partial(self.id.as_deref(), self.name.as_deref())
+    }
+}
+
+impl ReactionEmoji for APIEmoji {
+    fn to_path_segment(&self) -> String {
+        self.base.to_path_segment()
+    }
+}
+
+impl ReactionEmoji for APIMessageComponentEmoji {
+    fn to_path_segment(&self) -> String {
+        partial(self.id.as_deref(), self.name.as_deref())
+    }
+}
+
+fn partial(id: Option<&str>, name: Option<&str>) -> String {
+    match id {
+        Some(id) => custom(name, id),
+        None => encode_component(name.unwrap_or_default()),
+    }
+}
+
+/// Deleted custom emoji have no name; Discord matches on the id alone.
+fn custom(name: Option<&str>, id: &str) -> String {
+    let name = name.filter(|n| !n.is_empty()).unwrap_or("_");
+    format!("{}:{}", encode_component(name), encode_component(id))
+}
+
+impl Http {
+    /// PUT /channels/{channel_id}/messages/{message_id}/reactions/{emoji}/@me
+    pub async fn create_reaction<E: ReactionEmoji + ?Sized>(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        emoji: &E,
+    ) -> Result<(), HttpError> {
+        let emoji = emoji.to_path_segment();
+        let path = format!("/channels/{channel_id}/messages/{message_id}/reactions/{emoji}/@me");
+        let resp = self.request(Method::PUT, &path, |req| req).await?;
+        HttpError::check(resp).await?;
+        Ok(())
+    }
+
+    /// DELETE /channels/{channel_id}/messages/{message_id}/reactions/{emoji}/@me
+    pub async fn delete_own_reaction<E: ReactionEmoji + ?Sized>(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        emoji: &E,
+    ) -> Result<(), HttpError> {
+        let emoji = emoji.to_path_segment();
+        let path = format!("/channels/{channel_id}/messages/{message_id}/reactions/{emoji}/@me");
+        let resp = self.request(Method::DELETE, &path, |req| req).await?;
+        HttpError::check(resp).await?;
+        Ok(())
+    }
+
+    /// DELETE /channels/{channel_id}/messages/{message_id}/reactions/{emoji}/{user_id}
+    pub async fn delete_user_reaction<E: ReactionEmoji + ?Sized>(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        emoji: &E,
+        user_id: &str,
+    ) -> Result<(), HttpError> {
+        let emoji = emoji.to_path_segment();
+        let path =
+            format!("/channels/{channel_id}/messages/{message_id}/reactions/{emoji}/{user_id}");
+        let resp = self.request(Method::DELETE, &path, |req| req).await?;
+        HttpError::check(resp).await?;
+        Ok(())
+    }
+
+    /// GET /channels/{channel_id}/messages/{message_id}/reactions/{emoji}
+    /// One page of users who reacted; pass the last id as `after` for the next.
+    pub async fn get_reactions<E: ReactionEmoji + ?Sized>(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        emoji: &E,
+        query: &RESTGetAPIChannelMessageReactionUsersQuery,
+    ) -> Result<Vec<APIUser>, HttpError> {
+        let emoji = emoji.to_path_segment();
+        let path = format!("/channels/{channel_id}/messages/{message_id}/reactions/{emoji}");
+        let resp = self
+            .request(Method::GET, &path, |req| req.query(query))
+            .await?;
+        Ok(HttpError::check(resp).await?.json().await?)
+    }
+
+    /// DELETE /channels/{channel_id}/messages/{message_id}/reactions
+    pub async fn delete_all_reactions(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+    ) -> Result<(), HttpError> {
+        let path = format!("/channels/{channel_id}/messages/{message_id}/reactions");
+        let resp = self.request(Method::DELETE, &path, |req| req).await?;
+        HttpError::check(resp).await?;
+        Ok(())
+    }
+
+    /// DELETE /channels/{channel_id}/messages/{message_id}/reactions/{emoji}
+    pub async fn delete_all_reactions_for_emoji<E: ReactionEmoji + ?Sized>(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        emoji: &E,
+    ) -> Result<(), HttpError> {
+        let emoji = emoji.to_path_segment();
+        let path = format!("/channels/{channel_id}/messages/{message_id}/reactions/{emoji}");
+        let resp = self.request(Method::DELETE, &path, |req| req).await?;
+        HttpError::check(resp).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unicode_emoji_are_percent_encoded() {
+        assert_eq!("🔥".to_path_segment(), "%F0%9F%94%A5");
+        // Variation selector.
+        assert_eq!("❤️".to_path_segment(), "%E2%9D%A4%EF%B8%8F");
+        // Zero-width joiner sequence.
+        assert_eq!("👩‍💻".to_path_segment(), "%F0%9F%91%A9%E2%80%8D%F0%9F%92%BB");
+    }
+
+    #[test]
+    fn custom_emoji_keep_name_and_id() {
+        for emoji in ["ferris:123", "<:ferris:123>"] {
+            assert_eq!(emoji.to_path_segment(), "ferris:123", "{emoji}");
+        }
+    }
+
+    #[test]
+    fn animated_emoji_drop_the_marker() {
+        for emoji in ["a:ferris:123", "<a:ferris:123>"] {
+            assert_eq!(emoji.to_path_segment(), "ferris:123", "{emoji}");
+        }
+        // A custom emoji that happens to be called `a`.
+        assert_eq!("a:123".to_path_segment(), "a:123");
+    }
+
+    #[test]
+    fn nameless_custom_emoji_use_a_placeholder() {
+        assert_eq!(":123".to_path_segment(), "_:123");
+        let deleted = APIPartialEmoji {
+            id: Some("123".to_owned()),
+            name: None,
+            animated: None,
+        };
+        assert_eq!(deleted.to_path_segment(), "_:123");
+    }
+}
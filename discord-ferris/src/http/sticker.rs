@@ -0,0 +1,35 @@
+//! Guild sticker uploads.
+
+use reqwest::Method;
+
+use super::attachment::{self, Attachment, Form};
+use super::{Http, HttpError, with_reason};
+use crate::models::payloads::APISticker;
+use crate::models::rest::sticker::RESTPostAPIGuildStickerFormDataBody;
+
+impl Http {
+    /// POST /guilds/{guild_id}/stickers
+    /// Sent as plain form fields plus `file`; stickers take no `payload_json`.
+    /// The upload is `file`; `body.file` is not sent.
+    pub async fn create_guild_sticker(
+        &self,
+        guild_id: &str,
+        body: &RESTPostAPIGuildStickerFormDataBody,
+        file: Attachment,
+        reason: Option<&str>,
+    ) -> Result<APISticker, HttpError> {
+        let path = format!("/guilds/{guild_id}/stickers");
+        let (filename, _, data) = attachment::read(file).await?;
+        let mut form = Form::new();
+        form.text("name", &body.name);
+        form.text("description", &body.description);
+        form.text("tags", &body.tags);
+        form.file("file", &filename, &data);
+        let resp = self
+            .request(Method::POST, &path, |req| {
+                with_reason(form.apply(req), reason)
+            })
+            .await?;
+        Ok(HttpError::check(resp).await?.json().await?)
+    }
+}
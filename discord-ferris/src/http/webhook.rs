@@ -0,0 +1,87 @@
+//! Webhook execution and webhook message endpoints.
+
+use reqwest::{Method, StatusCode};
+
+use super::{Http, HttpError};
+use crate::models::payloads::APIMessage;
+use crate::models::rest::webhook::{
+    RESTPatchAPIWebhookWithTokenMessageFormDataBody, RESTPatchAPIWebhookWithTokenMessageJSONBody,
+    RESTPatchAPIWebhookWithTokenMessageQuery, RESTPostAPIWebhookWithTokenFormDataBody,
+    RESTPostAPIWebhookWithTokenJSONBody, RESTPostAPIWebhookWithTokenQuery,
+};
+
+impl Http {
+    /// POST /webhooks/{webhook_id}/{webhook_token}
+    /// Returns the message only when `query.wait` is set.
+    pub async fn execute_webhook(
+        &self,
+        webhook_id: &str,
+        token: &str,
+        query: &RESTPostAPIWebhookWithTokenQuery,
+        body: &RESTPostAPIWebhookWithTokenJSONBody,
+    ) -> Result<Option<APIMessage>, HttpError> {
+        let path = format!("/webhooks/{webhook_id}/{token}");
+        let resp = self
+            .request(Method::POST, &path, |req| req.query(query).json(body))
+            .await?;
+        let resp = HttpError::check(resp).await?;
+        if resp.status() == StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+        Ok(Some(resp.json().await?))
+    }
+
+    /// [`Http::execute_webhook`] with files.
+    pub async fn execute_webhook_with_files(
+        &self,
+        webhook_id: &str,
+        token: &str,
+        query: &RESTPostAPIWebhookWithTokenQuery,
+        body: RESTPostAPIWebhookWithTokenFormDataBody,
+    ) -> Result<Option<APIMessage>, HttpError> {
+        let path = format!("/webhooks/{webhook_id}/{token}");
+        let form = body.into_form().await?;
+        let resp = self
+            .request(Method::POST, &path, |req| form.apply(req.query(query)))
+            .await?;
+        let resp = HttpError::check(resp).await?;
+        if resp.status() == StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+        Ok(Some(resp.json().await?))
+    }
+
+    /// PATCH /webhooks/{webhook_id}/{webhook_token}/messages/{message_id}
+    /// `message_id` may be `@original` for an interaction's response.
+    pub async fn edit_webhook_message(
+        &self,
+        webhook_id: &str,
+        token: &str,
+        message_id: &str,
+        query: &RESTPatchAPIWebhookWithTokenMessageQuery,
+        body: &RESTPatchAPIWebhookWithTokenMessageJSONBody,
+    ) -> Result<APIMessage, HttpError> {
+        let path = format!("/webhooks/{webhook_id}/{token}/messages/{message_id}");
+        let resp = self
+            .request(Method::PATCH, &path, |req| req.query(query).json(body))
+            .await?;
+        Ok(HttpError::check(resp).await?.json().await?)
+    }
+
+    /// [`Http::edit_webhook_message`] with files.
+    pub async fn edit_webhook_message_with_files(
+        &self,
+        webhook_id: &str,
+        token: &str,
+        message_id: &str,
+        query: &RESTPatchAPIWebhookWithTokenMessageQuery,
+        body: RESTPatchAPIWebhookWithTokenMessageFormDataBody,
+    ) -> Result<APIMessage, HttpError> {
+        let path = format!("/webhooks/{webhook_id}/{token}/messages/{message_id}");
+        let form = body.into_form().await?;
+        let resp = self
+            .request(Method::PATCH, &path, |req| form.apply(req.query(query)))
+            .await?;
+        Ok(HttpError::check(resp).await?.json().await?)
+    }
+}
@@ -167,6 +167,28 @@ pub enum GatewayCloseCodes {
     DisallowedIntents = 4014,
 }
 
+impl GatewayCloseCodes {
+    pub const fn from_code(code: u16) -> Option<Self> {
+        Some(match code {
+            4000 => Self::UnknownError,
+            4001 => Self::UnknownOpcode,
+            4002 => Self::DecodeError,
+            4003 => Self::NotAuthenticated,
+            4004 => Self::AuthenticationFailed,
+            4005 => Self::AlreadyAuthenticated,
+            4007 => Self::InvalidSeq,
+            4008 => Self::RateLimited,
+            4009 => Self::SessionTimedOut,
+            4010 => Self::InvalidShard,
+            4011 => Self::ShardingRequired,
+            4012 => Self::InvalidAPIVersion,
+            4013 => Self::InvalidIntents,
+            4014 => Self::DisallowedIntents,
+            _ => return None,
+        })
+    }
+}
+
 use bitflags::bitflags;
 
 bitflags! {
@@ -304,6 +326,20 @@ pub enum GatewayDispatchEvents {
     VoiceServerUpdate,
     VoiceStateUpdate,
     WebhooksUpdate,
+    /// Any event this version does not know yet, by its raw name.
+    #[serde(untagged)]
+    Unknown(String),
+}
+
+impl GatewayDispatchEvents {
+    /// Parses a dispatch name such as `"MESSAGE_CREATE"`; names this version
+    /// does not know become [`Unknown`](Self::Unknown).
+    pub fn from_name(name: &str) -> Self {
+        use serde::de::IntoDeserializer;
+        let de: serde::de::value::StrDeserializer<'_, serde::de::value::Error> =
+            name.into_deserializer();
+        Self::deserialize(de).unwrap_or_else(|_| Self::Unknown(name.to_owned()))
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
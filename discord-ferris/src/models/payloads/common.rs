@@ -83,7 +83,9 @@ pub struct RESTErrorFieldInformation {
     pub message: String,
 }
 
+/// Strict, so `_errors` next to nested fields parses as a map instead.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct RESTErrorGroupWrapper {
     #[serde(rename = "_errors")]
     pub errors: Vec<RESTErrorData>,
@@ -97,6 +99,8 @@ pub enum RESTErrorData {
     FieldInfo(RESTErrorFieldInformation),
     GroupWrapper(RESTErrorGroupWrapper),
     String(String),
+    /// The `_errors` list of a map that also has nested fields.
+    List(Vec<RESTErrorData>),
     Map(HashMap<String, RESTErrorData>),
 }
 
@@ -2,6 +2,7 @@
 // Adapted from discord-api-types (c) vladfrangu
 // Rust port and modifications (c) 2025 andrewdotdev
 
+use super::form_data::FormDataBody;
 use super::poll::RESTAPIPoll;
 use crate::models::payloads::{
     APIAllowedMentions, APIChannel, APIEmbed, APIExtendedInvite, APIFollowedChannel,
@@ -12,6 +13,7 @@ use crate::models::payloads::{
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use serde_repr::{Deserialize_repr, Serialize_repr};
 
 /// A helper for fields that accept either a `String` or an ISO8601 string timestamp.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -446,7 +448,7 @@ pub struct RESTPostAPIChannelMessageJSONBody {
 /**
  * @see {@link https://discord.com/developers/docs/resources/channel#create-message}
  */
-pub type RESTPostAPIChannelMessageFormDataBody = Value;
+pub type RESTPostAPIChannelMessageFormDataBody = FormDataBody<RESTPostAPIChannelMessageJSONBody>;
 
 /**
  * @see {@link https://discord.com/developers/docs/resources/channel#create-message}
@@ -506,7 +508,7 @@ pub struct RESTGetAPIChannelMessageReactionUsersQuery {
 /**
  * @see {@link https://discord.com/developers/docs/resources/channel#get-reactions-reaction-types}
  */
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, Serialize_repr, Deserialize_repr, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum ReactionType {
     Normal = 0,
@@ -583,7 +585,7 @@ pub struct RESTPatchAPIChannelMessageJSONBody {
 /**
  * @see {@link https://discord.com/developers/docs/resources/channel#edit-message}
  */
-pub type RESTPatchAPIChannelMessageFormDataBody = Value;
+pub type RESTPatchAPIChannelMessageFormDataBody = FormDataBody<RESTPatchAPIChannelMessageJSONBody>;
 
 /**
  * @see {@link https://discord.com/developers/docs/resources/channel#edit-message}
@@ -0,0 +1,118 @@
+//! Files sent together with a JSON body.
+//!
+//! [`crate::http`] encodes these as `multipart/form-data`: the body goes into
+//! the `payload_json` part and file `n` into `files[n]`.
+
+use std::path::PathBuf;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Where the contents of an [`Attachment`] come from.
+enum Source {
+    Bytes(Vec<u8>),
+    Path(PathBuf),
+    Reader(Box<dyn AsyncRead + Send + Unpin>),
+}
+
+/// A file to upload. Paths and readers are read when the request is sent.
+pub struct Attachment {
+    filename: String,
+    description: Option<String>,
+    source: Source,
+}
+
+impl std::fmt::Debug for Attachment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let source = match &self.source {
+            Source::Bytes(data) => format!("{} bytes", data.len()),
+            Source::Path(path) => path.display().to_string(),
+            Source::Reader(_) => "reader".to_owned(),
+        };
+        f.debug_struct("Attachment")
+            .field("filename", &self.filename)
+            .field("description", &self.description)
+            .field("source", &source)
+            .finish()
+    }
+}
+
+impl Attachment {
+    /// A file with the given contents.
+    pub fn from_bytes(filename: impl Into<String>, data: impl Into<Vec<u8>>) -> Self {
+        Self::new(filename.into(), Source::Bytes(data.into()))
+    }
+
+    /// The file at `path`, uploaded under its own file name.
+    pub fn from_path(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "file".to_owned());
+        Self::new(filename, Source::Path(path))
+    }
+
+    /// Everything `reader` yields until EOF.
+    pub fn from_reader(
+        filename: impl Into<String>,
+        reader: impl AsyncRead + Send + Unpin + 'static,
+    ) -> Self {
+        Self::new(filename.into(), Source::Reader(Box::new(reader)))
+    }
+
+    fn new(filename: String, source: Source) -> Self {
+        Self {
+            filename,
+            description: None,
+            source,
+        }
+    }
+
+    /// Uploads the file under another name.
+    pub fn filename(mut self, filename: impl Into<String>) -> Self {
+        self.filename = filename.into();
+        self
+    }
+
+    /// Alt text shown for the file (up to 1024 characters).
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Filename, description and contents; the filename is kept on failure.
+    pub(crate) async fn read(self) -> (String, Option<String>, std::io::Result<Vec<u8>>) {
+        let data = match self.source {
+            Source::Bytes(data) => Ok(data),
+            Source::Path(path) => tokio::fs::read(path).await,
+            Source::Reader(mut reader) => {
+                let mut data = vec![];
+                reader.read_to_end(&mut data).await.map(|_| data)
+            }
+        };
+        (self.filename, self.description, data)
+    }
+}
+
+/// A JSON body sent together with files.
+#[derive(Debug)]
+pub struct FormDataBody<T> {
+    pub payload_json: T,
+    /// Sent as `files[0]`, `files[1]`, ... in this order.
+    pub files: Vec<Attachment>,
+}
+
+impl<T> FormDataBody<T> {
+    pub fn new(payload_json: T) -> Self {
+        Self {
+            payload_json,
+            files: vec![],
+        }
+    }
+
+    /// Adds a file after the ones already added.
+    pub fn file(mut self, file: Attachment) -> Self {
+        self.files.push(file);
+        self
+    }
+}
@@ -2,6 +2,7 @@
 // Adapted from discord-api-types (c) vladfrangu
 // Rust port and modifications (c) 2025 andrewdotdev
 
+use super::form_data::FormDataBody;
 use super::webhook::{
     RESTDeleteAPIWebhookWithTokenMessageResult, RESTGetAPIWebhookWithTokenMessageResult,
     RESTPatchAPIWebhookWithTokenMessageFormDataBody, RESTPatchAPIWebhookWithTokenMessageJSONBody,
@@ -191,7 +192,8 @@ pub struct RESTPostAPIInteractionCallbackQuery {
 /**
  * @see {@link https://discord.com/developers/docs/interactions/receiving-and-responding#create-interaction-response}
  */
-pub type RESTPostAPIInteractionCallbackFormDataBody = Value;
+pub type RESTPostAPIInteractionCallbackFormDataBody =
+    FormDataBody<RESTPostAPIInteractionCallbackJSONBody>;
 
 /**
  * @see {@link https://discord.com/developers/docs/interactions/receiving-and-responding#create-interaction-response}
@@ -324,7 +326,8 @@ pub type RESTPostAPIInteractionFollowupJSONBody = APIInteractionResponseCallback
 /**
  * @see {@link https://discord.com/developers/docs/interactions/receiving-and-responding#create-followup-message}
  */
-pub type RESTPostAPIInteractionFollowupFormDataBody = Value;
+pub type RESTPostAPIInteractionFollowupFormDataBody =
+    FormDataBody<RESTPostAPIInteractionFollowupJSONBody>;
 
 /**
  * @see {@link https://discord.com/developers/docs/interactions/receiving-and-responding#create-followup-message}
@@ -8,6 +8,7 @@ pub mod auto_moderation;
 pub mod channel;
 pub mod common;
 pub mod emoji;
+pub mod form_data;
 pub mod gateway;
 pub mod guild;
 pub mod guild_scheduled_event;
@@ -39,6 +40,8 @@ pub use common::*;
 #[allow(ambiguous_glob_reexports, unused_imports)]
 pub use emoji::*;
 #[allow(ambiguous_glob_reexports, unused_imports)]
+pub use form_data::*;
+#[allow(ambiguous_glob_reexports, unused_imports)]
 pub use gateway::*;
 #[allow(ambiguous_glob_reexports, unused_imports)]
 pub use guild::*;
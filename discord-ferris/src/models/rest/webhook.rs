@@ -3,12 +3,12 @@
 // Rust port and modifications (c) 2025 andrewdotdev
 
 use super::channel::RESTAPIAttachment;
+use super::form_data::FormDataBody;
 use super::poll::RESTAPIPoll;
 use crate::models::payloads::{
     APIAllowedMentions, APIEmbed, APIMessage, APIMessageTopLevelComponent, APIWebhook, MessageFlags,
 };
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 /**
  * @see {@link https://discord.com/developers/docs/resources/webhook#create-webhook}
@@ -196,7 +196,8 @@ pub struct RESTPostAPIWebhookWithTokenJSONBody {
 /**
  * @see {@link https://discord.com/developers/docs/resources/webhook#execute-webhook}
  */
-pub type RESTPostAPIWebhookWithTokenFormDataBody = Value;
+pub type RESTPostAPIWebhookWithTokenFormDataBody =
+    FormDataBody<RESTPostAPIWebhookWithTokenJSONBody>;
 
 /**
  * @see {@link https://discord.com/developers/docs/resources/webhook#execute-webhook-query-string-params}
@@ -356,7 +357,8 @@ pub struct RESTPatchAPIWebhookWithTokenMessageJSONBody {
 /**
  * @see {@link https://discord.com/developers/docs/resources/webhook#edit-webhook-message}
  */
-pub type RESTPatchAPIWebhookWithTokenMessageFormDataBody = Value;
+pub type RESTPatchAPIWebhookWithTokenMessageFormDataBody =
+    FormDataBody<RESTPatchAPIWebhookWithTokenMessageJSONBody>;
 
 /**
  * @see {@link https://discord.com/developers/docs/resources/webhook#edit-webhook-message-query-string-params}
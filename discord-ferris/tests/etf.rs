@@ -0,0 +1,170 @@
+//! ETF codec round trips against fixtures from `tests/fixtures/etf/generate.py`.
+
+use discord_ferris::gateway::etf;
+use discord_ferris::models::gateway::{
+    GatewayHelloData, GatewayMessageCreateDispatchData, GatewayReadyDispatchData,
+};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+
+const RECEIVED: &[&str] = &[
+    "hello",
+    "heartbeat_ack",
+    "invalid_session",
+    "ready",
+    "message_create",
+];
+
+fn fixture(name: &str) -> Vec<u8> {
+    let path = format!("{}/tests/fixtures/etf/{name}", env!("CARGO_MANIFEST_DIR"));
+    std::fs::read(&path).unwrap_or_else(|e| panic!("{path}: {e}"))
+}
+
+fn fixture_json(name: &str) -> Value {
+    serde_json::from_slice(&fixture(&format!("{name}.json"))).unwrap()
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Frame<D> {
+    op: u8,
+    s: Option<i64>,
+    t: Option<String>,
+    d: D,
+}
+
+/// Decodes the ETF fixture straight into `D` and checks it against the JSON twin.
+fn decode_model<D: DeserializeOwned + PartialEq + std::fmt::Debug>(name: &str) -> Frame<D> {
+    let from_etf: Frame<D> = etf::from_slice(&fixture(&format!("{name}.etf"))).unwrap();
+    let from_json: Frame<D> = serde_json::from_value(fixture_json(name)).unwrap();
+    assert_eq!(from_etf, from_json);
+    from_etf
+}
+
+#[test]
+fn transcodes_received_payloads_to_json() {
+    for name in RECEIVED {
+        let raw = etf::to_json(&fixture(&format!("{name}.etf"))).unwrap();
+        let value: Value = serde_json::from_str(raw.get()).unwrap();
+        assert_eq!(value, fixture_json(name), "{name}");
+    }
+}
+
+#[test]
+fn reencodes_received_payloads() {
+    for name in RECEIVED {
+        let value: Value = etf::from_slice(&fixture(&format!("{name}.etf"))).unwrap();
+        let bytes = etf::to_vec(&value).unwrap();
+        let again: Value = etf::from_slice(&bytes).unwrap();
+        assert_eq!(again, value, "{name}");
+    }
+}
+
+#[test]
+fn decodes_hello() {
+    let hello = decode_model::<GatewayHelloData>("hello");
+    assert_eq!(hello.op, 10);
+    assert_eq!(hello.d.heartbeat_interval, 41250);
+}
+
+#[test]
+fn decodes_ready() {
+    let ready = decode_model::<GatewayReadyDispatchData>("ready");
+    assert_eq!(ready.t.as_deref(), Some("READY"));
+    assert_eq!(ready.d.user.id, "1111111111111111111");
+    assert_eq!(ready.d.guilds[1].base.id, "333333333333333333");
+    assert_eq!(ready.d.shard, Some((0, 1)));
+    assert_eq!(ready.d.application.flags, 565248);
+}
+
+#[test]
+fn decodes_message_create() {
+    let msg = decode_model::<GatewayMessageCreateDispatchData>("message_create");
+    assert_eq!(msg.s, Some(42));
+    assert_eq!(msg.d.message.id, "1234567890123456789");
+    assert_eq!(msg.d.message.author.id, "555555555555555555");
+    assert_eq!(msg.d.message.content, "!ping ünïcödé");
+    assert_eq!(msg.d.extra.guild_id.as_deref(), Some("222222222222222222"));
+}
+
+#[test]
+fn decodes_invalid_session() {
+    let invalid = decode_model::<bool>("invalid_session");
+    assert!(!invalid.d);
+}
+
+#[test]
+fn models_round_trip() {
+    let ready = decode_model::<GatewayReadyDispatchData>("ready").d;
+    let bytes = etf::to_vec(&ready).unwrap();
+    assert_eq!(
+        etf::from_slice::<GatewayReadyDispatchData>(&bytes).unwrap(),
+        ready
+    );
+
+    let msg = decode_model::<GatewayMessageCreateDispatchData>("message_create").d;
+    let bytes = etf::to_vec(&msg).unwrap();
+    assert_eq!(
+        etf::from_slice::<GatewayMessageCreateDispatchData>(&bytes).unwrap(),
+        msg
+    );
+}
+
+#[test]
+fn encodes_sent_payloads() {
+    let identify = json!({
+        "op": 2,
+        "d": {
+            "token": "Bot token",
+            "intents": 33281,
+            "shard": [0, 1],
+            "properties": { "os": "linux", "browser": "discord-ferris", "device": "discord-ferris" }
+        }
+    });
+    assert_eq!(
+        etf::to_vec(&identify).unwrap(),
+        fixture("sent_identify.etf")
+    );
+
+    let resume = json!({
+        "op": 6,
+        "d": { "token": "Bot token", "session_id": "0123456789abcdef0123456789abcdef", "seq": 1337 }
+    });
+    assert_eq!(etf::to_vec(&resume).unwrap(), fixture("sent_resume.etf"));
+
+    let heartbeat = json!({ "op": 1, "d": 251 });
+    assert_eq!(
+        etf::to_vec(&heartbeat).unwrap(),
+        fixture("sent_heartbeat.etf")
+    );
+
+    let heartbeat = json!({ "op": 1, "d": null });
+    assert_eq!(
+        etf::to_vec(&heartbeat).unwrap(),
+        fixture("sent_heartbeat_null.etf")
+    );
+}
+
+#[test]
+fn round_trips_scalars() {
+    let value = json!({
+        "float": 1.5,
+        "negative": -70000,
+        "small": 7,
+        "list": [1, "two", null, true],
+        "empty": [],
+        "map": {},
+    });
+    let bytes = etf::to_vec(&value).unwrap();
+    assert_eq!(etf::from_slice::<Value>(&bytes).unwrap(), value);
+
+    // Big integers decode as numbers into integer fields and as strings otherwise.
+    let bytes = etf::to_vec(&u64::MAX).unwrap();
+    assert_eq!(etf::from_slice::<u64>(&bytes).unwrap(), u64::MAX);
+    assert_eq!(
+        etf::from_slice::<String>(&bytes).unwrap(),
+        u64::MAX.to_string()
+    );
+    let bytes = etf::to_vec(&i64::MIN).unwrap();
+    assert_eq!(etf::from_slice::<i64>(&bytes).unwrap(), i64::MIN);
+}
@@ -0,0 +1,216 @@
+#!/usr/bin/env python3
+"""Regenerates the ETF gateway fixtures used by tests/etf.rs.
+
+Received payloads (`*.etf`) follow what Discord's gateway sends with
+`encoding=etf`: atom keys, binary strings, `nil` for null and snowflakes as
+SMALL_BIG_EXT integers. Each one has a `*.json` twin holding the equivalent
+JSON payload (snowflakes as strings), which is what the decoder must produce.
+
+Sent payloads (`sent_*.etf`) pin the bytes the library writes for IDENTIFY,
+RESUME and heartbeats: binary keys in sorted order, `nil` for null.
+
+This encoder is deliberately independent of the Rust implementation.
+Run from this directory: `python3 generate.py`.
+"""
+
+import json
+import struct
+
+
+class Snowflake(int):
+    """An ID: a big integer in ETF, a string in JSON."""
+
+
+class Legacy(str):
+    """A key encoded as a Latin-1 ATOM_EXT."""
+
+
+def atom(name, legacy=False):
+    data = name.encode("latin-1" if legacy else "utf-8")
+    if legacy:
+        return bytes([100]) + struct.pack(">H", len(data)) + data
+    return bytes([119, len(data)]) + data
+
+
+def binary(data):
+    return bytes([109]) + struct.pack(">I", len(data)) + data
+
+
+def integer(n, big=False):
+    if not big and 0 <= n <= 255:
+        return bytes([97, n])
+    if not big and -(2**31) <= n < 2**31:
+        return bytes([98]) + struct.pack(">i", n)
+    magnitude = abs(n)
+    digits = magnitude.to_bytes(max(1, (magnitude.bit_length() + 7) // 8), "little")
+    return bytes([110, len(digits), 1 if n < 0 else 0]) + digits
+
+
+def term(value, atom_keys):
+    if value is None:
+        return atom("nil")
+    if value is True:
+        return atom("true")
+    if value is False:
+        return atom("false")
+    if isinstance(value, Snowflake):
+        return integer(int(value), big=True)
+    if isinstance(value, int):
+        return integer(value)
+    if isinstance(value, float):
+        return bytes([70]) + struct.pack(">d", value)
+    if isinstance(value, str):
+        return binary(value.encode("utf-8"))
+    if isinstance(value, list):
+        if not value:
+            return bytes([106])
+        # Erlang packs lists of bytes as STRING_EXT; the library writes lists.
+        if atom_keys and all(type(v) is int and 0 <= v <= 255 for v in value):
+            return bytes([107]) + struct.pack(">H", len(value)) + bytes(value)
+        body = b"".join(term(v, atom_keys) for v in value)
+        return bytes([108]) + struct.pack(">I", len(value)) + body + bytes([106])
+    if isinstance(value, dict):
+        out = bytes([116]) + struct.pack(">I", len(value))
+        items = value.items() if atom_keys else sorted(value.items())
+        for k, v in items:
+            if isinstance(k, Legacy):
+                out += atom(k, legacy=True)
+            elif atom_keys:
+                out += atom(k)
+            else:
+                out += binary(k.encode("utf-8"))
+            out += term(v, atom_keys)
+        return out
+    raise TypeError(value)
+
+
+def to_json(value):
+    if isinstance(value, Snowflake):
+        return str(int(value))
+    if isinstance(value, list):
+        return [to_json(v) for v in value]
+    if isinstance(value, dict):
+        return {str(k): to_json(v) for k, v in value.items()}
+    return value
+
+
+def write(name, value, atom_keys=True, with_json=True):
+    with open(f"{name}.etf", "wb") as f:
+        f.write(bytes([131]) + term(value, atom_keys))
+    if with_json:
+        with open(f"{name}.json", "w") as f:
+            json.dump(to_json(value), f, indent=2, sort_keys=True)
+            f.write("\n")
+
+
+USER = {
+    "id": Snowflake(1111111111111111111),
+    "username": "ferris",
+    "discriminator": "0",
+    "global_name": "Ferris 🦀",
+    "avatar": None,
+    "bot": True,
+    "flags": 0,
+    "public_flags": 524288,
+}
+
+write(
+    "hello",
+    {
+        "op": 10,
+        "s": None,
+        "t": None,
+        "d": {"heartbeat_interval": 41250, "_trace": ['["gateway-prd-us-east1-b-0568",{"micros":0.0}]']},
+    },
+)
+
+write(
+    "heartbeat_ack",
+    {Legacy("op"): 11, Legacy("s"): None, Legacy("t"): None, Legacy("d"): None},
+)
+
+write("invalid_session", {"op": 9, "s": None, "t": None, "d": False})
+
+write(
+    "ready",
+    {
+        "op": 0,
+        "s": 1,
+        "t": "READY",
+        "d": {
+            "v": 10,
+            "user": USER,
+            "guilds": [
+                {"id": Snowflake(222222222222222222), "unavailable": True},
+                {"id": Snowflake(333333333333333333), "unavailable": True},
+            ],
+            "session_id": "0123456789abcdef0123456789abcdef",
+            "resume_gateway_url": "wss://gateway-us-east1-b.discord.gg",
+            "shard": [0, 1],
+            "application": {"id": Snowflake(1111111111111111111), "flags": 565248},
+            "_trace": ['["gateway-prd-us-east1-b-0568",{"micros":76291}]'],
+        },
+    },
+)
+
+write(
+    "message_create",
+    {
+        "op": 0,
+        "s": 42,
+        "t": "MESSAGE_CREATE",
+        "d": {
+            "id": Snowflake(1234567890123456789),
+            "channel_id": Snowflake(444444444444444444),
+            "guild_id": Snowflake(222222222222222222),
+            "author": dict(USER, bot=None, id=Snowflake(555555555555555555), username="crab"),
+            "member": {
+                "roles": [],
+                "nick": None,
+                "joined_at": "2024-01-01T00:00:00.000000+00:00",
+                "deaf": False,
+                "mute": False,
+                "flags": 0,
+            },
+            "content": "!ping ünïcödé",
+            "timestamp": "2025-08-01T12:34:56.789000+00:00",
+            "edited_timestamp": None,
+            "tts": False,
+            "mention_everyone": False,
+            "mentions": [],
+            "mention_roles": [],
+            "attachments": [],
+            "embeds": [],
+            "nonce": Snowflake(1400000000000000000),
+            "pinned": False,
+            "type": 0,
+            "flags": 0,
+            "components": [],
+        },
+    },
+)
+
+write(
+    "sent_identify",
+    {
+        "op": 2,
+        "d": {
+            "token": "Bot token",
+            "intents": 33281,
+            "shard": [0, 1],
+            "properties": {"os": "linux", "browser": "discord-ferris", "device": "discord-ferris"},
+        },
+    },
+    atom_keys=False,
+    with_json=False,
+)
+
+write(
+    "sent_resume",
+    {"op": 6, "d": {"token": "Bot token", "session_id": "0123456789abcdef0123456789abcdef", "seq": 1337}},
+    atom_keys=False,
+    with_json=False,
+)
+
+write("sent_heartbeat", {"op": 1, "d": 251}, atom_keys=False, with_json=False)
+write("sent_heartbeat_null", {"op": 1, "d": None}, atom_keys=False, with_json=False)
@@ -0,0 +1,98 @@
+//! `Client::login` against a local REST endpoint and gateway.
+
+use std::time::Duration;
+
+use discord_ferris::client::Client;
+use discord_ferris::gateway::GatewayError;
+use discord_ferris::models::gateway::{GatewayCloseCodes, GatewayIntents};
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+/// Answers every request with a one-shard GET /gateway/bot body.
+async fn serve_gateway_bot(listener: TcpListener, gateway_url: String) {
+    let body = serde_json::json!({
+        "url": gateway_url,
+        "shards": 1,
+        "session_start_limit": {
+            "total": 1000,
+            "remaining": 1000,
+            "reset_after": 0,
+            "max_concurrency": 1,
+        },
+    })
+    .to_string();
+    loop {
+        let Ok((mut socket, _)) = listener.accept().await else {
+            return;
+        };
+        let body = body.clone();
+        tokio::spawn(async move {
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                match socket.read(&mut buf).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _ = socket.write_all(response.as_bytes()).await;
+        });
+    }
+}
+
+/// Sends HELLO, waits for IDENTIFY and closes with `code`.
+async fn serve_gateway(listener: TcpListener, code: u16) {
+    let (socket, _) = listener.accept().await.unwrap();
+    let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+    let hello = r#"{"op":10,"d":{"heartbeat_interval":45000}}"#;
+    ws.send(Message::Text(hello.into())).await.unwrap();
+    while let Some(Ok(msg)) = ws.next().await {
+        if let Message::Text(text) = msg
+            && text.contains(r#""op":2"#)
+        {
+            break;
+        }
+    }
+    let _ = ws
+        .send(Message::Close(Some(CloseFrame {
+            code: CloseCode::from(code),
+            reason: "".into(),
+        })))
+        .await;
+    // Let the client read the close frame before the socket goes away.
+    while let Some(Ok(_)) = ws.next().await {}
+}
+
+#[tokio::test]
+async fn fatal_close_code_fails_login() {
+    let rest = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let gateway = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let api_base_url = format!("http://{}", rest.local_addr().unwrap());
+    let gateway_url = format!("ws://{}", gateway.local_addr().unwrap());
+    tokio::spawn(serve_gateway_bot(rest, gateway_url));
+    tokio::spawn(serve_gateway(gateway, 4004));
+
+    let mut client = Client::builder("token", GatewayIntents::GUILDS)
+        .api_base_url(api_base_url)
+        .ctrl_c(false)
+        .build()
+        .unwrap();
+    let result = tokio::time::timeout(Duration::from_secs(10), client.login())
+        .await
+        .expect("login did not return");
+    assert!(
+        matches!(
+            result,
+            Err(GatewayError::Fatal(GatewayCloseCodes::AuthenticationFailed))
+        ),
+        "{result:?}"
+    );
+}
@@ -0,0 +1,111 @@
+//! Recorded gateway traffic replayed through a `Router`, without a network.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use discord_ferris::framework::context::{Context, Ctx};
+use discord_ferris::framework::router::Router;
+use discord_ferris::gateway::record::Replay;
+use discord_ferris::http::Http;
+use discord_ferris::models::gateway::GatewayDispatchEvents;
+use serde::Deserialize;
+
+const RECORDING: &str = r#"
+{"ts":1000,"shard":0,"op":10,"d":{"heartbeat_interval":41250}}
+{"ts":1010,"shard":0,"op":0,"t":"MESSAGE_CREATE","s":1,"d":{"id":"1","channel_id":"2","content":"hi"}}
+{"ts":1020,"shard":1,"op":0,"t":"SOME_FUTURE_EVENT","s":2,"d":{"answer":42}}
+{"ts":1030,"shard":0,"op":11}
+"#;
+
+#[derive(Deserialize)]
+struct Message {
+    content: String,
+}
+
+/// Writes the recording to a file of its own per test, then reads it back.
+fn replay(test: &str) -> Replay {
+    let path = std::env::temp_dir().join(format!(
+        "discord-ferris-{test}-{}.jsonl",
+        std::process::id()
+    ));
+    std::fs::write(&path, RECORDING).unwrap();
+    let replay = Replay::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    replay
+}
+
+fn ctx() -> Ctx {
+    Ctx::new(Arc::new(Context::new(Arc::new(Http::new("token")))))
+}
+
+#[tokio::test]
+async fn dispatches_only_op0_frames() {
+    let replay = replay("op0");
+    assert_eq!(replay.frames().len(), 4);
+
+    let seen = Arc::new(AtomicUsize::new(0));
+    let mut router = Router::new();
+    let counter = Arc::clone(&seen);
+    router.on_all(move |_| {
+        let counter = Arc::clone(&counter);
+        async move {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }
+    });
+
+    assert_eq!(replay.run(&router, &ctx()).await, 2);
+    assert_eq!(seen.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn typed_and_raw_handlers_see_recorded_payloads() {
+    let contents = Arc::new(std::sync::Mutex::new(vec![]));
+    let mut router = Router::new();
+
+    let sink = Arc::clone(&contents);
+    router.on::<Message, _, _>(GatewayDispatchEvents::MessageCreate, move |c, m| {
+        let sink = Arc::clone(&sink);
+        async move {
+            sink.lock().unwrap().push((c.shard_id(), m.content));
+        }
+    });
+    let sink = Arc::clone(&contents);
+    router.on_raw("SOME_FUTURE_EVENT", move |c, raw| {
+        let sink = Arc::clone(&sink);
+        async move {
+            sink.lock()
+                .unwrap()
+                .push((c.shard_id(), raw.get().to_owned()));
+        }
+    });
+
+    replay("handlers").run(&router, &ctx()).await;
+    assert_eq!(
+        *contents.lock().unwrap(),
+        [(0, "hi".to_owned()), (1, r#"{"answer":42}"#.to_owned())]
+    );
+}
+
+#[tokio::test]
+async fn unknown_events_reach_on_unknown() {
+    let names = Arc::new(std::sync::Mutex::new(vec![]));
+    let mut router = Router::new();
+    let sink = Arc::clone(&names);
+    router.on_unknown(move |c| {
+        let sink = Arc::clone(&sink);
+        async move {
+            sink.lock().unwrap().push(c.event_name());
+        }
+    });
+
+    replay("unknown").run(&router, &ctx()).await;
+    assert_eq!(
+        *names.lock().unwrap(),
+        [
+            Some(GatewayDispatchEvents::MessageCreate),
+            Some(GatewayDispatchEvents::Unknown(
+                "SOME_FUTURE_EVENT".to_owned()
+            )),
+        ]
+    );
+}